@@ -1,7 +1,9 @@
 use crate::auth::models::*;
-use crate::database::{DatabasePool, DatabaseService};
+use crate::config::Settings;
+use crate::database::{DatabasePool, DatabaseService, UserEntry};
+use crate::rate_limit::{self, RateLimitBucket, RateLimiter};
 use actix_session::Session;
-use actix_web::{web, HttpResponse, Result, ResponseError};
+use actix_web::{web, HttpRequest, HttpResponse, Result, ResponseError};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use log::{error, info, warn};
 use rand::Rng;
@@ -37,16 +39,50 @@ impl From<anyhow::Error> for AuthError {
     }
 }
 
+// Passkey and account settings, read once at startup
+pub struct AuthConfig {
+    // Skip WebAuthn credential validation (development and automated tests only)
+    pub test_mode: bool,
+    admin_usernames: Vec<String>,
+    pub rp_id: String,
+    pub rp_name: String,
+    pub origin: String,
+}
+
+impl AuthConfig {
+    // Read TEST_MODE, ADMIN_USERNAMES (comma-separated), WEBAUTHN_RP_ID, WEBAUTHN_RP_NAME and
+    // WEBAUTHN_ORIGIN
+    pub fn from_env(settings: &Settings) -> Self {
+        AuthConfig {
+            test_mode: settings.flag("TEST_MODE"),
+            admin_usernames: settings
+                .get("ADMIN_USERNAMES")
+                .unwrap_or_default()
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect(),
+            rp_id: settings
+                .get("WEBAUTHN_RP_ID")
+                .unwrap_or_else(|| "localhost".to_string()),
+            rp_name: settings
+                .get("WEBAUTHN_RP_NAME")
+                .unwrap_or_else(|| "Thalora URL Shortener".to_string()),
+            origin: settings
+                .get("WEBAUTHN_ORIGIN")
+                .unwrap_or_else(|| "http://localhost:3000".to_string()),
+        }
+    }
+
+    // Check whether a username is listed in ADMIN_USERNAMES
+    pub fn is_admin_username(&self, username: &str) -> bool {
+        self.admin_usernames.iter().any(|name| name == username)
+    }
+}
+
 pub struct AuthService;
 
 impl AuthService {
-    // Check if test mode is enabled
-    pub fn is_test_mode() -> bool {
-        std::env::var("TEST_MODE")
-            .unwrap_or_else(|_| "false".to_string())
-            .to_lowercase()
-            == "true"
-    }
 
     // Generate a cryptographic challenge for WebAuthn
     pub fn generate_challenge() -> Vec<u8> {
@@ -180,6 +216,7 @@ pub async fn register_begin(
     req: web::Json<RegisterBeginRequest>,
     session: Session,
     db_pool: web::Data<DatabasePool>,
+    auth_config: web::Data<AuthConfig>,
 ) -> Result<HttpResponse> {
     let username = req.username.trim().to_string();
     let email = req.email.trim().to_lowercase();
@@ -263,8 +300,8 @@ pub async fn register_begin(
         user_id: user_id_b64.clone(),
         timeout: 60000, // 60 seconds
         rp: RelyingParty {
-            id: std::env::var("WEBAUTHN_RP_ID").unwrap_or_else(|_| "localhost".to_string()),
-            name: std::env::var("WEBAUTHN_RP_NAME").unwrap_or_else(|_| "Thalora URL Shortener".to_string()),
+            id: auth_config.rp_id.clone(),
+            name: auth_config.rp_name.clone(),
         },
         user: UserInfo {
             id: user_id_b64,
@@ -297,6 +334,7 @@ pub async fn register_complete(
     req: web::Json<RegisterCompleteRequest>,
     session: Session,
     db_pool: web::Data<DatabasePool>,
+    auth_config: web::Data<AuthConfig>,
 ) -> Result<HttpResponse> {
     info!("Completing registration for user ID: {}", req.user_id);
 
@@ -358,15 +396,14 @@ pub async fn register_complete(
     }
 
     // Validate credential (or skip in test mode)
-    let (credential_id, public_key) = if AuthService::is_test_mode() {
+    let (credential_id, public_key) = if auth_config.test_mode {
         info!("Test mode enabled - bypassing credential validation");
         // Generate fake credential data for test mode
         let fake_credential_id = format!("test-credential-{}", username).into_bytes();
         let fake_public_key = vec![0u8; 65]; // Fake 65-byte public key
         (fake_credential_id, fake_public_key)
     } else {
-        let expected_origin = std::env::var("WEBAUTHN_ORIGIN").unwrap_or_else(|_| "http://localhost:3000".to_string());
-        match AuthService::validate_registration_credential(&req.credential, stored_challenge, &expected_origin).await {
+        match AuthService::validate_registration_credential(&req.credential, stored_challenge, &auth_config.origin).await {
             Ok((credential_id, public_key)) => (credential_id, public_key),
             Err(e) => {
                 error!("Credential validation failed: {}", e);
@@ -414,9 +451,24 @@ pub async fn register_complete(
 
 pub async fn login_begin(
     req: web::Json<LoginBeginRequest>,
+    http_req: HttpRequest,
     session: Session,
     db_pool: web::Data<DatabasePool>,
+    rate_limiter: web::Data<RateLimiter>,
+    auth_config: web::Data<AuthConfig>,
 ) -> Result<HttpResponse> {
+    if let Some(response) =
+        rate_limit::enforce(
+            &rate_limiter,
+            RateLimitBucket::Login,
+            None,
+            &session,
+            &http_req,
+        )
+    {
+        return Ok(response);
+    }
+
     let username = req.username.trim();
 
     info!("Beginning login for user: {}", username);
@@ -460,7 +512,7 @@ pub async fn login_begin(
     let response = LoginBeginResponse {
         challenge: challenge_b64,
         timeout: 60000, // 60 seconds
-        rp_id: std::env::var("WEBAUTHN_RP_ID").unwrap_or_else(|_| "localhost".to_string()),
+        rp_id: auth_config.rp_id.clone(),
         allow_credentials: vec![AllowedCredential {
             id: AuthService::encode_base64(&user.passkey_credential_id),
             cred_type: "public-key".to_string(),
@@ -475,6 +527,7 @@ pub async fn login_complete(
     req: web::Json<LoginCompleteRequest>,
     session: Session,
     db_pool: web::Data<DatabasePool>,
+    auth_config: web::Data<AuthConfig>,
 ) -> Result<HttpResponse> {
     info!("Completing login for user: {}", req.username);
 
@@ -532,15 +585,14 @@ pub async fn login_complete(
     };
 
     // Validate credential (or skip in test mode)
-    let new_counter = if AuthService::is_test_mode() {
+    let new_counter = if auth_config.test_mode {
         info!("Test mode enabled - bypassing authentication credential validation");
         user.passkey_counter + 1 // Just increment counter in test mode
     } else {
-        let expected_origin = std::env::var("WEBAUTHN_ORIGIN").unwrap_or_else(|_| "http://localhost:3000".to_string());
         match AuthService::validate_authentication_credential(
             &req.credential,
             stored_challenge,
-            &expected_origin,
+            &auth_config.origin,
             &user.passkey_public_key,
             user.passkey_counter,
         ).await {
@@ -621,8 +673,50 @@ pub async fn me(
     }
 }
 
-pub async fn test_mode_info() -> Result<HttpResponse> {
+// Resolve the signed-in user and ensure they are an administrator.
+// Returns the response to send when the caller is not allowed.
+pub async fn require_admin(
+    session: &Session,
+    db_pool: &DatabasePool,
+    auth_config: &AuthConfig,
+) -> Result<UserEntry, HttpResponse> {
+    let user_id: i64 = match session.get("user_id") {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            return Err(HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "Not authenticated"
+            })));
+        }
+        Err(e) => {
+            error!("Session error: {}", e);
+            return Err(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Session error"
+            })));
+        }
+    };
+
+    match DatabaseService::get_user_by_id(db_pool, user_id).await {
+        Ok(Some(user)) if auth_config.is_admin_username(&user.username) => Ok(user),
+        Ok(Some(user)) => {
+            warn!("User {} (ID: {}) attempted an admin action", user.username, user.id);
+            Err(HttpResponse::Forbidden().json(serde_json::json!({
+                "error": "Administrator access required"
+            })))
+        }
+        Ok(None) => Err(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "User not found"
+        }))),
+        Err(e) => {
+            error!("Database error retrieving user: {}", e);
+            Err(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Database error"
+            })))
+        }
+    }
+}
+
+pub async fn test_mode_info(auth_config: web::Data<AuthConfig>) -> Result<HttpResponse> {
     Ok(HttpResponse::Ok().json(serde_json::json!({
-        "test_mode": AuthService::is_test_mode()
+        "test_mode": auth_config.test_mode
     })))
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod auth;
 pub mod models;
 // Middleware implementation will be added in future versions
\ No newline at end of file
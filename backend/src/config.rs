@@ -0,0 +1,363 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// Layered application configuration: environment variables override values from the optional
+// CONFIG_FILE, which override each setting's built-in default. Variables in a `.env` file are loaded
+// into the environment by dotenv before CONFIG_FILE is read, so they also take precedence over it.
+//
+// The file holds the same settings as the environment, either as flat keys (`DB_MAX_CONNECTIONS = 20`)
+// or grouped in tables whose names are joined with underscores (`[db] max_connections = 20`).
+// Arrays become comma-separated values, e.g. `ALLOWED_ORIGINS = ["https://a", "https://b"]`.
+//
+// The merged values are read once at startup and handed to each component's `from_env`
+// constructor; the process environment itself is never modified.
+pub struct AppConfig {
+    // Path of the loaded configuration file, if any
+    pub file: Option<String>,
+    // Settings taken from the file, i.e. not already set in the environment
+    pub file_settings: Vec<String>,
+    // File values with the environment merged on top
+    pub settings: Settings,
+    // Most items each batch endpoint accepts per request
+    pub batch_limits: BatchLimits,
+}
+
+// Configuration values by environment-style name (e.g. DB_MAX_CONNECTIONS)
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    values: BTreeMap<String, String>,
+}
+
+impl Settings {
+    // File values overridden by environment variables of the same name
+    fn merge(
+        file_values: BTreeMap<String, String>,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Self {
+        let mut values = file_values;
+        values.extend(env);
+        Settings { values }
+    }
+
+    // The environment alone (variables that are not valid UTF-8 are ignored)
+    pub fn from_env() -> Self {
+        Self::merge(BTreeMap::new(), process_env())
+    }
+
+    #[cfg(test)]
+    pub fn from_pairs(pairs: &[(&str, &str)]) -> Self {
+        Settings {
+            values: pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.values.get(key).cloned()
+    }
+
+    // A true/false setting; anything other than "true" (any case) is false
+    pub fn flag(&self, key: &str) -> bool {
+        self.values
+            .get(key)
+            .is_some_and(|value| value.to_lowercase() == "true")
+    }
+}
+
+fn process_env() -> impl Iterator<Item = (String, String)> {
+    std::env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+}
+
+// Highest MAX_BATCH_STATUS accepted: the status lookup binds one query parameter per short ID and
+// SQL Server allows at most 2100 per statement
+const MAX_STATUS_BATCH_LIMIT: usize = 2000;
+
+// Per-request item caps for the batch endpoints
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchLimits {
+    // POST /api/shorten/batch-with-defaults
+    pub shorten: usize,
+    // POST /api/urls/status/batch
+    pub status: usize,
+    // POST /api/urls/import (rows per CSV file)
+    pub import: usize,
+}
+
+impl BatchLimits {
+    // Read MAX_BATCH_SHORTEN (default 100), MAX_BATCH_STATUS (default 500, at most 2000) and
+    // MAX_BATCH_IMPORT (default 1000)
+    pub fn from_env(settings: &Settings) -> anyhow::Result<Self> {
+        let status = parse_batch_limit("MAX_BATCH_STATUS", settings.get("MAX_BATCH_STATUS"), 500)?;
+        if status > MAX_STATUS_BATCH_LIMIT {
+            return Err(anyhow::anyhow!(
+                "Invalid MAX_BATCH_STATUS '{}'. At most {} short IDs can be checked per request",
+                status,
+                MAX_STATUS_BATCH_LIMIT
+            ));
+        }
+
+        Ok(BatchLimits {
+            shorten: parse_batch_limit(
+                "MAX_BATCH_SHORTEN",
+                settings.get("MAX_BATCH_SHORTEN"),
+                100,
+            )?,
+            status,
+            import: parse_batch_limit("MAX_BATCH_IMPORT", settings.get("MAX_BATCH_IMPORT"), 1000)?,
+        })
+    }
+}
+
+fn parse_batch_limit(name: &str, value: Option<String>, default: usize) -> anyhow::Result<usize> {
+    match value {
+        Some(value) => value
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|limit| *limit > 0)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid {} '{}'. Expected a positive number of items per request",
+                    name,
+                    value
+                )
+            }),
+        None => Ok(default),
+    }
+}
+
+impl AppConfig {
+    // Load CONFIG_FILE (TOML, or JSON by .json extension) and merge the environment over it.
+    // Must run at startup, after dotenv, before any component is configured.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let path = match std::env::var("CONFIG_FILE") {
+            Ok(path) if !path.trim().is_empty() => path,
+            _ => {
+                let settings = Settings::from_env();
+                return Ok(AppConfig {
+                    file: None,
+                    file_settings: Vec::new(),
+                    batch_limits: BatchLimits::from_env(&settings)?,
+                    settings,
+                });
+            }
+        };
+
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("Failed to read CONFIG_FILE '{}': {}", path, e))?;
+        let file_values = parse_config_file(&path, &contents)?;
+
+        let env: BTreeMap<String, String> = process_env().collect();
+        let file_settings = file_values
+            .keys()
+            .filter(|key| !env.contains_key(*key))
+            .cloned()
+            .collect();
+        let settings = Settings::merge(file_values, env);
+
+        Ok(AppConfig {
+            file: Some(path),
+            file_settings,
+            batch_limits: BatchLimits::from_env(&settings)?,
+            settings,
+        })
+    }
+}
+
+// Parse a configuration file into environment-style KEY=value pairs
+pub fn parse_config_file(path: &str, contents: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let is_json = Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+    let document: serde_json::Value = if is_json {
+        serde_json::from_str(contents)
+            .map_err(|e| anyhow::anyhow!("Invalid JSON in CONFIG_FILE '{}': {}", path, e))?
+    } else {
+        toml::from_str(contents)
+            .map_err(|e| anyhow::anyhow!("Invalid TOML in CONFIG_FILE '{}': {}", path, e))?
+    };
+
+    let table = document.as_object().ok_or_else(|| {
+        anyhow::anyhow!("CONFIG_FILE '{}' must contain a table of settings", path)
+    })?;
+
+    let mut values = BTreeMap::new();
+    flatten_settings("", table, &mut values)?;
+    Ok(values)
+}
+
+fn flatten_settings(
+    prefix: &str,
+    table: &serde_json::Map<String, serde_json::Value>,
+    values: &mut BTreeMap<String, String>,
+) -> anyhow::Result<()> {
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name.to_uppercase()
+        } else {
+            format!("{}_{}", prefix, name.to_uppercase())
+        };
+
+        if let serde_json::Value::Object(nested) = value {
+            flatten_settings(&key, nested, values)?;
+            continue;
+        }
+
+        let value = match value {
+            serde_json::Value::Array(items) => items
+                .iter()
+                .map(|item| scalar_to_string(&key, item))
+                .collect::<anyhow::Result<Vec<_>>>()?
+                .join(","),
+            _ => scalar_to_string(&key, value)?,
+        };
+
+        if values.insert(key.clone(), value).is_some() {
+            return Err(anyhow::anyhow!(
+                "Setting {} is defined more than once in CONFIG_FILE",
+                key
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn scalar_to_string(key: &str, value: &serde_json::Value) -> anyhow::Result<String> {
+    match value {
+        serde_json::Value::String(s) => Ok(s.clone()),
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        serde_json::Value::Bool(b) => Ok(b.to_string()),
+        _ => Err(anyhow::anyhow!(
+            "Setting {} in CONFIG_FILE must be a string, number, boolean or list of those",
+            key
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_toml_flat_and_grouped_settings() {
+        let values = parse_config_file(
+            "thalora.toml",
+            r#"
+            ENVIRONMENT = "staging"
+            ALLOWED_ORIGINS = ["https://a.example.com", "https://b.example.com"]
+
+            [db]
+            max_connections = 20
+            encryption_enabled = true
+
+            [rate_limit]
+            shorten_per_minute = 60
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(values["ENVIRONMENT"], "staging");
+        assert_eq!(
+            values["ALLOWED_ORIGINS"],
+            "https://a.example.com,https://b.example.com"
+        );
+        assert_eq!(values["DB_MAX_CONNECTIONS"], "20");
+        assert_eq!(values["DB_ENCRYPTION_ENABLED"], "true");
+        assert_eq!(values["RATE_LIMIT_SHORTEN_PER_MINUTE"], "60");
+    }
+
+    #[test]
+    fn test_parse_json_settings() {
+        let values = parse_config_file(
+            "config/thalora.JSON",
+            r#"{"webauthn": {"rp_id": "example.com"}, "SERVER_PORT": 8081}"#,
+        )
+        .unwrap();
+
+        assert_eq!(values["WEBAUTHN_RP_ID"], "example.com");
+        assert_eq!(values["SERVER_PORT"], "8081");
+    }
+
+    #[test]
+    fn test_example_config_file_parses() {
+        let values = parse_config_file(
+            "config.example.toml",
+            include_str!("../config.example.toml"),
+        )
+        .unwrap();
+
+        assert_eq!(values["SERVER_HOST"], "0.0.0.0");
+        assert_eq!(values["WEBAUTHN_RP_ID"], "staging.example.com");
+        assert_eq!(values["DB_MAX_CONNECTIONS"], "20");
+        assert_eq!(values["MAX_BATCH_SHORTEN"], "100");
+    }
+
+    #[test]
+    fn test_environment_overrides_file_settings() {
+        let file_values = parse_config_file(
+            "thalora.toml",
+            "SERVER_PORT = 8081\n[db]\nmax_connections = 20\n",
+        )
+        .unwrap();
+        let settings = Settings::merge(
+            file_values,
+            [("SERVER_PORT".to_string(), "9090".to_string())],
+        );
+
+        assert_eq!(settings.get("SERVER_PORT").as_deref(), Some("9090"));
+        assert_eq!(settings.get("DB_MAX_CONNECTIONS").as_deref(), Some("20"));
+        assert_eq!(settings.get("DB_MIN_CONNECTIONS"), None);
+    }
+
+    #[test]
+    fn test_flag_settings() {
+        let settings =
+            Settings::from_pairs(&[("TEST_MODE", "TRUE"), ("TOLERATE_SLUG_SUFFIX", "yes")]);
+        assert!(settings.flag("TEST_MODE"));
+        assert!(!settings.flag("TOLERATE_SLUG_SUFFIX"));
+        assert!(!settings.flag("MEDIA_TYPE_ENRICHMENT"));
+    }
+
+    #[test]
+    fn test_parse_batch_limit() {
+        assert_eq!(
+            parse_batch_limit("MAX_BATCH_SHORTEN", None, 100).unwrap(),
+            100
+        );
+        assert_eq!(
+            parse_batch_limit("MAX_BATCH_SHORTEN", Some(" 250 ".to_string()), 100).unwrap(),
+            250
+        );
+        assert!(parse_batch_limit("MAX_BATCH_SHORTEN", Some("0".to_string()), 100).is_err());
+        assert!(parse_batch_limit("MAX_BATCH_SHORTEN", Some("lots".to_string()), 100).is_err());
+    }
+
+    #[test]
+    fn test_status_batch_limit_is_capped() {
+        let limits = |value: &str| {
+            BatchLimits::from_env(&Settings::from_pairs(&[("MAX_BATCH_STATUS", value)]))
+        };
+
+        assert_eq!(limits("2000").unwrap().status, MAX_STATUS_BATCH_LIMIT);
+        assert!(limits("2001").is_err());
+        assert!(limits("3000").is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_settings() {
+        assert!(parse_config_file("thalora.toml", "DB_MAX_CONNECTIONS = ").is_err());
+        assert!(parse_config_file("thalora.json", "[1, 2]").is_err());
+        assert!(parse_config_file("thalora.json", r#"{"SERVER_PORT": null}"#).is_err());
+        // The same setting written flat and grouped
+        assert!(parse_config_file(
+            "thalora.toml",
+            "DB_MAX_CONNECTIONS = 5\n[db]\nmax_connections = 10\n"
+        )
+        .is_err());
+    }
+}
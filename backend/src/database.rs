@@ -3,7 +3,7 @@ use bb8::Pool;
 use bb8_tiberius::ConnectionManager;
 use log::{info, warn};
 use serde::{Deserialize, Serialize};
-use std::env;
+use crate::config::Settings;
 use tiberius::Config;
 use chrono::{DateTime, Utc};
 
@@ -12,18 +12,60 @@ pub type DatabasePool = Pool<ConnectionManager>;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UrlEntry {
     pub id: i64,
+    pub user_id: Option<i64>,
     pub original_url: String,
     pub shortened_url: String,
+    pub base_url: Option<String>,
+    pub media_type: Option<String>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    // Overrides EXPIRED_LINK_GRACE_SECONDS for this link
+    pub expiry_grace_seconds: Option<i64>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
+// Who created a link: the owning user, or for anonymous links the client IP. Admin-only.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UrlCreator {
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub ip: Option<String>,
+}
+
+// A link to create
+#[derive(Debug, Clone)]
+pub struct NewUrl {
+    pub original_url: String,
+    pub shortened_url: String,
+    pub base_url: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub expiry_grace_seconds: Option<i64>,
+    // Tags to attach, created for the owner as needed. Tags belong to a user, so only insert_urls
+    // (which always has an owner) stores them.
+    pub tags: Vec<String>,
+}
+
+// Fields to change on a link; None leaves a field untouched, Some(None) clears a nullable field
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UrlUpdate {
+    pub original_url: Option<String>,
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+    pub expiry_grace_seconds: Option<Option<i64>>,
+}
+
+impl UrlUpdate {
+    pub fn is_empty(&self) -> bool {
+        self == &UrlUpdate::default()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DomainEntry {
     pub id: i64,
     pub user_id: Option<i64>,
     pub domain_name: String,
     pub is_verified: bool,
+    pub is_default: bool,
     pub verification_token: Option<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
@@ -39,6 +81,8 @@ pub struct UserEntry {
     pub passkey_counter: u32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    // Named rate limit tier (free, pro or enterprise)
+    pub rate_limit_tier: String,
 }
 
 #[derive(Debug, Clone)]
@@ -50,35 +94,36 @@ pub struct DatabaseConfig {
 }
 
 impl DatabaseConfig {
-    pub fn from_env() -> Result<Self> {
-        let base_connection_string = env::var("DATABASE_URL")
-            .map_err(|_| anyhow::anyhow!("DATABASE_URL environment variable not set"))?;
+    pub fn from_env(settings: &Settings) -> Result<Self> {
+        let base_connection_string = settings
+            .get("DATABASE_URL")
+            .ok_or_else(|| anyhow::anyhow!("DATABASE_URL environment variable not set"))?;
 
         // Validate connection string has database parameter
         Self::validate_database_name(&base_connection_string)?;
 
         // Parse environment variables for pool configuration
-        let max_connections = env::var("DB_MAX_CONNECTIONS")
-            .ok()
+        let max_connections = settings
+            .get("DB_MAX_CONNECTIONS")
             .and_then(|s| s.parse().ok())
             .unwrap_or(10);
 
-        let min_connections = env::var("DB_MIN_CONNECTIONS")
-            .ok()
+        let min_connections = settings
+            .get("DB_MIN_CONNECTIONS")
             .and_then(|s| s.parse().ok())
             .unwrap_or(1);
 
         // Determine if we should enable encryption based on environment
-        let encryption_enabled = env::var("DB_ENCRYPTION_ENABLED")
-            .ok()
+        let encryption_enabled = settings
+            .get("DB_ENCRYPTION_ENABLED")
             .and_then(|s| s.parse().ok())
             .unwrap_or_else(|| {
                 // Default: disable encryption for local development, enable for production
-                let is_production = env::var("ENVIRONMENT")
-                    .unwrap_or_else(|_| "development".to_string())
+                settings
+                    .get("ENVIRONMENT")
+                    .unwrap_or_else(|| "development".to_string())
                     .to_lowercase()
-                    == "production";
-                is_production
+                    == "production"
             });
 
         // Build connection string with appropriate encryption settings
@@ -222,14 +267,175 @@ pub async fn create_connection_pool(config: &DatabaseConfig) -> Result<DatabaseP
     Ok(pool)
 }
 
+// Columns selected for every UrlEntry, in the order url_entry_from_row expects
+const URL_ENTRY_COLUMNS: &str =
+    "id, user_id, original_url, shortened_url, base_url, media_type, expires_at, expiry_grace_seconds, created_at, updated_at";
+
+fn url_entry_from_row(row: &tiberius::Row) -> UrlEntry {
+    let id: i64 = row.get(0).unwrap();
+    let user_id: Option<i64> = row.get(1);
+    let original_url: &str = row.get(2).unwrap();
+    let shortened_url: &str = row.get(3).unwrap();
+    let base_url: Option<&str> = row.get(4);
+    let media_type: Option<&str> = row.get(5);
+    let expires_at: Option<chrono::DateTime<chrono::Utc>> = row.get(6);
+    let expiry_grace_seconds: Option<i64> = row.get(7);
+    let created_at: chrono::DateTime<chrono::Utc> = row.get(8).unwrap();
+    let updated_at: chrono::DateTime<chrono::Utc> = row.get(9).unwrap();
+
+    UrlEntry {
+        id,
+        user_id,
+        original_url: original_url.to_string(),
+        shortened_url: shortened_url.to_string(),
+        base_url: base_url.map(|s| s.to_string()),
+        media_type: media_type.map(|s| s.to_string()),
+        expires_at,
+        expiry_grace_seconds,
+        created_at,
+        updated_at,
+    }
+}
+
+// Columns selected for every DomainEntry, in the order domain_entry_from_row expects
+const DOMAIN_ENTRY_COLUMNS: &str =
+    "id, user_id, domain_name, is_verified, is_default, verification_token, created_at, updated_at";
+
+fn domain_entry_from_row(row: &tiberius::Row) -> DomainEntry {
+    let id: i64 = row.get(0).unwrap();
+    let user_id: Option<i64> = row.get(1);
+    let domain_name: &str = row.get(2).unwrap();
+    let is_verified: bool = row.get(3).unwrap();
+    let is_default: bool = row.get(4).unwrap();
+    let verification_token: Option<&str> = row.get(5);
+    let created_at: chrono::DateTime<chrono::Utc> = row.get(6).unwrap();
+    let updated_at: chrono::DateTime<chrono::Utc> = row.get(7).unwrap();
+
+    DomainEntry {
+        id,
+        user_id,
+        domain_name: domain_name.to_string(),
+        is_verified,
+        is_default,
+        verification_token: verification_token.map(|s| s.to_string()),
+        created_at,
+        updated_at,
+    }
+}
+
+// Maximum rows updated per statement inside a bulk transaction (SQL Server allows 2100 parameters)
+const BULK_UPDATE_BATCH_SIZE: usize = 500;
+
+async fn begin_transaction(conn: &mut bb8_tiberius::rt::Client) -> Result<()> {
+    conn.simple_query("BEGIN TRANSACTION")
+        .await?
+        .into_results()
+        .await?;
+    Ok(())
+}
+
+// Commit when the work succeeded, otherwise roll back so the pooled connection is left clean
+async fn finish_transaction<T>(conn: &mut bb8_tiberius::rt::Client, result: Result<T>) -> Result<T> {
+    match result {
+        Ok(value) => {
+            conn.simple_query("COMMIT TRANSACTION")
+                .await?
+                .into_results()
+                .await?;
+            Ok(value)
+        }
+        Err(e) => {
+            let rollback = async {
+                conn.simple_query("IF @@TRANCOUNT > 0 ROLLBACK TRANSACTION")
+                    .await?
+                    .into_results()
+                    .await
+            }
+            .await;
+            if let Err(rollback_error) = rollback {
+                warn!("Failed to roll back transaction: {}", rollback_error);
+            }
+            Err(e)
+        }
+    }
+}
+
+// SET clause for a link update, binding the changed values from @P2 in field order. A new target
+// clears media_type so enrichment categorizes the new target on its next redirect.
+fn url_update_set_clause(update: &UrlUpdate) -> String {
+    let mut columns = Vec::new();
+    if update.original_url.is_some() {
+        columns.push("original_url");
+    }
+    if update.expires_at.is_some() {
+        columns.push("expires_at");
+    }
+    if update.expiry_grace_seconds.is_some() {
+        columns.push("expiry_grace_seconds");
+    }
+
+    // Column names come from the fixed list above; values are always bound as parameters
+    let mut assignments: Vec<String> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| format!("{} = @P{}", column, i + 2))
+        .collect();
+    if update.original_url.is_some() {
+        assignments.push("media_type = NULL".to_string());
+    }
+    assignments.push("updated_at = GETUTCDATE()".to_string());
+
+    assignments.join(", ")
+}
+
+// Attach tags to a link, creating any of the owner's tags that do not exist yet
+async fn attach_tags(
+    conn: &mut bb8_tiberius::rt::Client,
+    user_id: i64,
+    url_id: i64,
+    tags: &[String],
+) -> Result<()> {
+    for tag in tags {
+        let mut query = tiberius::Query::new(
+            "
+            IF NOT EXISTS (SELECT 1 FROM tags WITH (UPDLOCK, HOLDLOCK) WHERE user_id = @P1 AND name = @P2)
+                INSERT INTO tags (user_id, name) VALUES (@P1, @P2);
+            INSERT INTO url_tags (url_id, tag_id)
+            SELECT @P3, id FROM tags WHERE user_id = @P1 AND name = @P2",
+        );
+        query.bind(user_id);
+        query.bind(tag.as_str());
+        query.bind(url_id);
+        query.execute(conn).await?;
+    }
+    Ok(())
+}
+
+async fn insert_audit_log_entry(
+    conn: &mut bb8_tiberius::rt::Client,
+    user_id: Option<i64>,
+    action: &str,
+    details: &serde_json::Value,
+) -> Result<()> {
+    let mut query = tiberius::Query::new(
+        "INSERT INTO audit_log (user_id, action, details) VALUES (@P1, @P2, @P3)",
+    );
+    query.bind(user_id);
+    query.bind(action);
+    query.bind(details.to_string());
+    query.execute(conn).await?;
+    Ok(())
+}
+
 pub struct DatabaseService;
 
 impl DatabaseService {
 
     pub async fn insert_url(
         pool: &DatabasePool,
-        original_url: &str,
-        shortened_url: &str,
+        url: &NewUrl,
+        user_id: Option<i64>,
+        creator_ip: Option<&str>,
     ) -> Result<i64> {
         let mut conn = pool
             .get()
@@ -237,13 +443,18 @@ impl DatabaseService {
             .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
 
         let query = "
-            INSERT INTO urls (original_url, shortened_url) 
+            INSERT INTO urls (original_url, shortened_url, user_id, base_url, expires_at, expiry_grace_seconds, creator_ip) 
             OUTPUT INSERTED.id
-            VALUES (@P1, @P2)";
+            VALUES (@P1, @P2, @P3, @P4, @P5, @P6, @P7)";
 
         let mut query = tiberius::Query::new(query);
-        query.bind(original_url);
-        query.bind(shortened_url);
+        query.bind(url.original_url.as_str());
+        query.bind(url.shortened_url.as_str());
+        query.bind(user_id);
+        query.bind(url.base_url.as_str());
+        query.bind(url.expires_at);
+        query.bind(url.expiry_grace_seconds);
+        query.bind(creator_ip);
 
         let stream = query.query(&mut *conn).await?;
         let row = stream.into_first_result().await?;
@@ -257,16 +468,90 @@ impl DatabaseService {
         }
     }
 
-    pub async fn get_original_url(
+    // Insert several links for one owner, with their tags, in a single transaction; returns the new
+    // IDs in order
+    pub async fn insert_urls(
+        pool: &DatabasePool,
+        user_id: i64,
+        urls: &[NewUrl],
+    ) -> Result<Vec<i64>> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        begin_transaction(&mut conn).await?;
+        let result = async {
+            let mut ids = Vec::with_capacity(urls.len());
+            for url in urls {
+                let mut query = tiberius::Query::new(
+                    "
+            INSERT INTO urls (original_url, shortened_url, user_id, base_url, expires_at, expiry_grace_seconds) 
+            OUTPUT INSERTED.id
+            VALUES (@P1, @P2, @P3, @P4, @P5, @P6)",
+                );
+                query.bind(url.original_url.as_str());
+                query.bind(url.shortened_url.as_str());
+                query.bind(user_id);
+                query.bind(url.base_url.as_str());
+                query.bind(url.expires_at);
+                query.bind(url.expiry_grace_seconds);
+
+                let row = query.query(&mut *conn).await?.into_row().await?;
+                let id: i64 = row
+                    .and_then(|row| row.get(0))
+                    .ok_or_else(|| anyhow::anyhow!("Failed to insert URL"))?;
+                attach_tags(&mut conn, user_id, id, &url.tags).await?;
+                ids.push(id);
+            }
+            Ok(ids)
+        }
+        .await;
+        let ids = finish_transaction(&mut conn, result).await?;
+
+        info!("Inserted {} URLs for user {}", ids.len(), user_id);
+        Ok(ids)
+    }
+
+    // The creator of a link, joining the owner to their username; None when no link has the given ID
+    pub async fn get_url_creator(pool: &DatabasePool, url_id: i64) -> Result<Option<UrlCreator>> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        let query = "
+            SELECT urls.user_id, users.username, urls.creator_ip
+            FROM urls
+            LEFT JOIN users ON users.id = urls.user_id
+            WHERE urls.id = @P1";
+
+        let mut query = tiberius::Query::new(query);
+        query.bind(url_id);
+
+        let stream = query.query(&mut *conn).await?;
+        let row = stream.into_row().await?;
+
+        Ok(row.map(|row| UrlCreator {
+            user_id: row.get(0),
+            username: row.get::<&str, _>(1).map(|s| s.to_string()),
+            ip: row.get::<&str, _>(2).map(|s| s.to_string()),
+        }))
+    }
+
+    pub async fn get_url_by_short_id(
         pool: &DatabasePool,
         shortened_url: &str,
-    ) -> Result<Option<String>> {
+    ) -> Result<Option<UrlEntry>> {
         let mut conn = pool
             .get()
             .await
             .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
 
-        let query = "SELECT original_url FROM urls WHERE shortened_url = @P1";
+        let query = format!(
+            "SELECT {} FROM urls WHERE shortened_url = @P1",
+            URL_ENTRY_COLUMNS
+        );
 
         let mut query = tiberius::Query::new(query);
         query.bind(shortened_url);
@@ -274,12 +559,120 @@ impl DatabaseService {
         let stream = query.query(&mut *conn).await?;
         let row = stream.into_first_result().await?;
 
-        if let Some(row) = row.into_iter().next() {
-            let original_url: &str = row.get(0).unwrap();
-            Ok(Some(original_url.to_string()))
-        } else {
-            Ok(None)
+        Ok(row.first().map(url_entry_from_row))
+    }
+
+    // Look up many links by short ID in one query. Callers keep the list within SQL Server's
+    // 2100-parameter limit.
+    pub async fn get_urls_by_short_ids(
+        pool: &DatabasePool,
+        shortened_urls: &[String],
+    ) -> Result<Vec<UrlEntry>> {
+        if shortened_urls.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        let placeholders = (1..=shortened_urls.len())
+            .map(|i| format!("@P{}", i))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let query = format!(
+            "SELECT {} FROM urls WHERE shortened_url IN ({})",
+            URL_ENTRY_COLUMNS, placeholders
+        );
+
+        let mut query = tiberius::Query::new(query);
+        for shortened_url in shortened_urls {
+            query.bind(shortened_url.as_str());
+        }
+
+        let stream = query.query(&mut *conn).await?;
+        let rows = stream.into_first_result().await?;
+
+        Ok(rows.iter().map(url_entry_from_row).collect())
+    }
+
+    pub async fn get_urls_by_user(pool: &DatabasePool, user_id: i64) -> Result<Vec<UrlEntry>> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        let query = format!(
+            "SELECT {} FROM urls WHERE user_id = @P1 ORDER BY created_at DESC",
+            URL_ENTRY_COLUMNS
+        );
+
+        let mut query = tiberius::Query::new(query);
+        query.bind(user_id);
+
+        let stream = query.query(&mut *conn).await?;
+        let rows = stream.into_first_result().await?;
+
+        Ok(rows.iter().map(url_entry_from_row).collect())
+    }
+
+    // Tag names of one link, sorted by name
+    pub async fn get_url_tags(pool: &DatabasePool, url_id: i64) -> Result<Vec<String>> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        let query = "
+            SELECT t.name
+            FROM url_tags ut
+            JOIN tags t ON t.id = ut.tag_id
+            WHERE ut.url_id = @P1
+            ORDER BY t.name";
+
+        let mut query = tiberius::Query::new(query);
+        query.bind(url_id);
+
+        let stream = query.query(&mut *conn).await?;
+        let rows = stream.into_first_result().await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| row.get::<&str, _>(0).map(|name| name.to_string()))
+            .collect())
+    }
+
+    // Tag names of the user's tagged links, keyed by link ID and sorted by name
+    pub async fn get_link_tags_by_user(
+        pool: &DatabasePool,
+        user_id: i64,
+    ) -> Result<std::collections::HashMap<i64, Vec<String>>> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        let query = "
+            SELECT ut.url_id, t.name
+            FROM url_tags ut
+            JOIN tags t ON t.id = ut.tag_id
+            WHERE t.user_id = @P1
+            ORDER BY t.name";
+
+        let mut query = tiberius::Query::new(query);
+        query.bind(user_id);
+
+        let stream = query.query(&mut *conn).await?;
+        let rows = stream.into_first_result().await?;
+
+        let mut tags: std::collections::HashMap<i64, Vec<String>> = std::collections::HashMap::new();
+        for row in rows {
+            let url_id: i64 = row.get(0).unwrap();
+            let name: &str = row.get(1).unwrap();
+            tags.entry(url_id).or_default().push(name.to_string());
         }
+        Ok(tags)
     }
 
     pub async fn url_exists(pool: &DatabasePool, shortened_url: &str) -> Result<bool> {
@@ -304,6 +697,171 @@ impl DatabaseService {
         }
     }
 
+    // Move a link's expiry; returns false when no link has the given ID
+    pub async fn update_url_expiry(
+        pool: &DatabasePool,
+        url_id: i64,
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        let query = "
+            UPDATE urls
+            SET expires_at = @P2, updated_at = GETUTCDATE()
+            WHERE id = @P1";
+
+        let mut query = tiberius::Query::new(query);
+        query.bind(url_id);
+        query.bind(expires_at);
+
+        let result = query.execute(&mut *conn).await?;
+        Ok(result.total() > 0)
+    }
+
+    // Apply every provided field in one UPDATE within a transaction; returns false when no link has
+    // the given ID
+    pub async fn update_url(pool: &DatabasePool, url_id: i64, update: &UrlUpdate) -> Result<bool> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        let query = format!(
+            "UPDATE urls SET {} WHERE id = @P1",
+            url_update_set_clause(update)
+        );
+
+        let mut query = tiberius::Query::new(query);
+        query.bind(url_id);
+        if let Some(original_url) = &update.original_url {
+            query.bind(original_url.as_str());
+        }
+        if let Some(expires_at) = update.expires_at {
+            query.bind(expires_at);
+        }
+        if let Some(expiry_grace_seconds) = update.expiry_grace_seconds {
+            query.bind(expiry_grace_seconds);
+        }
+
+        begin_transaction(&mut conn).await?;
+        let result = async { Ok(query.execute(&mut *conn).await?.total() > 0) }.await;
+        finish_transaction(&mut conn, result).await
+    }
+
+    // Count a redirect against today's (UTC) total for the link
+    pub async fn record_click(pool: &DatabasePool, url_id: i64) -> Result<()> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        // HOLDLOCK keeps concurrent first clicks of a day from racing to insert the same row
+        let query = "
+            MERGE url_clicks_daily WITH (HOLDLOCK) AS target
+            USING (SELECT @P1 AS url_id, CAST(GETUTCDATE() AS DATE) AS day) AS source
+            ON target.url_id = source.url_id AND target.day = source.day
+            WHEN MATCHED THEN UPDATE SET clicks = target.clicks + 1
+            WHEN NOT MATCHED THEN INSERT (url_id, day, clicks) VALUES (source.url_id, source.day, 1);";
+
+        let mut query = tiberius::Query::new(query);
+        query.bind(url_id);
+        query.execute(&mut *conn).await?;
+        Ok(())
+    }
+
+    // Daily redirect counts for a link between two UTC dates inclusive; days without redirects are omitted
+    pub async fn get_daily_clicks(
+        pool: &DatabasePool,
+        url_id: i64,
+        start: chrono::NaiveDate,
+        end: chrono::NaiveDate,
+    ) -> Result<Vec<(chrono::NaiveDate, i64)>> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        let query = "
+            SELECT day, clicks
+            FROM url_clicks_daily
+            WHERE url_id = @P1 AND day BETWEEN @P2 AND @P3
+            ORDER BY day";
+
+        let mut query = tiberius::Query::new(query);
+        query.bind(url_id);
+        query.bind(start);
+        query.bind(end);
+
+        let stream = query.query(&mut *conn).await?;
+        let rows = stream.into_first_result().await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let day: chrono::NaiveDate = row.get(0).unwrap();
+                let clicks: i64 = row.get(1).unwrap();
+                (day, clicks)
+            })
+            .collect())
+    }
+
+    pub async fn update_url_media_type(
+        pool: &DatabasePool,
+        url_id: i64,
+        media_type: &str,
+    ) -> Result<bool> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        let query = "
+            UPDATE urls
+            SET media_type = @P2, updated_at = GETUTCDATE()
+            WHERE id = @P1 AND media_type IS NULL";
+
+        let mut query = tiberius::Query::new(query);
+        query.bind(url_id);
+        query.bind(media_type);
+
+        let result = query.execute(&mut *conn).await?;
+        Ok(result.total() > 0)
+    }
+
+    pub async fn get_media_type_counts(
+        pool: &DatabasePool,
+        user_id: i64,
+    ) -> Result<Vec<(String, i64)>> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        let query = "
+            SELECT media_type, COUNT_BIG(*)
+            FROM urls
+            WHERE user_id = @P1 AND media_type IS NOT NULL
+            GROUP BY media_type
+            ORDER BY media_type";
+
+        let mut query = tiberius::Query::new(query);
+        query.bind(user_id);
+        let stream = query.query(&mut *conn).await?;
+        let rows = stream.into_first_result().await?;
+
+        let mut counts = Vec::new();
+        for row in rows {
+            let media_type: &str = row.get(0).unwrap();
+            let count: i64 = row.get(1).unwrap();
+            counts.push((media_type.to_string(), count));
+        }
+
+        Ok(counts)
+    }
+
     // Domain management methods
     pub async fn insert_domain(
         pool: &DatabasePool,
@@ -349,10 +907,10 @@ impl DatabaseService {
             .await
             .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
 
-        let query = "
-            SELECT id, user_id, domain_name, is_verified, verification_token, created_at, updated_at 
-            FROM domains 
-            WHERE domain_name = @P1";
+        let query = format!(
+            "SELECT {} FROM domains WHERE domain_name = @P1",
+            DOMAIN_ENTRY_COLUMNS
+        );
 
         let mut query = tiberius::Query::new(query);
         query.bind(domain_name);
@@ -360,27 +918,7 @@ impl DatabaseService {
         let stream = query.query(&mut *conn).await?;
         let row = stream.into_first_result().await?;
 
-        if let Some(row) = row.into_iter().next() {
-            let id: i64 = row.get(0).unwrap();
-            let user_id: Option<i64> = row.get(1);
-            let domain_name: &str = row.get(2).unwrap();
-            let is_verified: bool = row.get(3).unwrap();
-            let verification_token: Option<&str> = row.get(4);
-            let created_at: chrono::DateTime<chrono::Utc> = row.get(5).unwrap();
-            let updated_at: chrono::DateTime<chrono::Utc> = row.get(6).unwrap();
-
-            Ok(Some(DomainEntry {
-                id,
-                user_id,
-                domain_name: domain_name.to_string(),
-                is_verified,
-                verification_token: verification_token.map(|s| s.to_string()),
-                created_at,
-                updated_at,
-            }))
-        } else {
-            Ok(None)
-        }
+        Ok(row.first().map(domain_entry_from_row))
     }
 
     pub async fn get_domain_by_id(
@@ -392,10 +930,7 @@ impl DatabaseService {
             .await
             .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
 
-        let query_sql = "
-            SELECT id, user_id, domain_name, is_verified, verification_token, created_at, updated_at 
-            FROM domains 
-            WHERE id = @P1";
+        let query_sql = format!("SELECT {} FROM domains WHERE id = @P1", DOMAIN_ENTRY_COLUMNS);
 
         let mut query = tiberius::Query::new(query_sql);
         query.bind(domain_id);
@@ -403,67 +938,31 @@ impl DatabaseService {
         let stream = query.query(&mut *conn).await?;
         let rows = stream.into_first_result().await?;
 
-        if let Some(row) = rows.into_iter().next() {
-            let id: i64 = row.get(0).unwrap();
-            let user_id: Option<i64> = row.get(1);
-            let domain_name: &str = row.get(2).unwrap();
-            let is_verified: bool = row.get(3).unwrap();
-            let verification_token: Option<&str> = row.get(4);
-            let created_at: chrono::DateTime<chrono::Utc> = row.get(5).unwrap();
-            let updated_at: chrono::DateTime<chrono::Utc> = row.get(6).unwrap();
-
-            Ok(Some(DomainEntry {
-                id,
-                user_id,
-                domain_name: domain_name.to_string(),
-                is_verified,
-                verification_token: verification_token.map(|s| s.to_string()),
-                created_at,
-                updated_at,
-            }))
-        } else {
-            Ok(None)
-        }
+        Ok(rows.first().map(domain_entry_from_row))
     }
 
-    pub async fn get_verified_domains(pool: &DatabasePool) -> Result<Vec<DomainEntry>> {
+    // Verified domains a caller may use: their own plus shared domains without an owner.
+    // Anonymous callers (None) only get the shared domains.
+    pub async fn get_verified_domains(
+        pool: &DatabasePool,
+        user_id: Option<i64>,
+    ) -> Result<Vec<DomainEntry>> {
         let mut conn = pool
             .get()
             .await
             .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
 
-        let query = "
-            SELECT id, user_id, domain_name, is_verified, verification_token, created_at, updated_at 
-            FROM domains 
-            WHERE is_verified = 1
-            ORDER BY created_at DESC";
+        let query = format!(
+            "SELECT {} FROM domains WHERE is_verified = 1 AND (user_id IS NULL OR user_id = @P1) ORDER BY created_at DESC",
+            DOMAIN_ENTRY_COLUMNS
+        );
 
-        let query = tiberius::Query::new(query);
+        let mut query = tiberius::Query::new(query);
+        query.bind(user_id);
         let stream = query.query(&mut *conn).await?;
         let rows = stream.into_first_result().await?;
 
-        let mut domains = Vec::new();
-        for row in rows {
-            let id: i64 = row.get(0).unwrap();
-            let user_id: Option<i64> = row.get(1);
-            let domain_name: &str = row.get(2).unwrap();
-            let is_verified: bool = row.get(3).unwrap();
-            let verification_token: Option<&str> = row.get(4);
-            let created_at: chrono::DateTime<chrono::Utc> = row.get(5).unwrap();
-            let updated_at: chrono::DateTime<chrono::Utc> = row.get(6).unwrap();
-
-            domains.push(DomainEntry {
-                id,
-                user_id,
-                domain_name: domain_name.to_string(),
-                is_verified,
-                verification_token: verification_token.map(|s| s.to_string()),
-                created_at,
-                updated_at,
-            });
-        }
-
-        Ok(domains)
+        Ok(rows.iter().map(domain_entry_from_row).collect())
     }
 
     pub async fn get_all_domains(pool: &DatabasePool) -> Result<Vec<DomainEntry>> {
@@ -472,37 +971,16 @@ impl DatabaseService {
             .await
             .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
 
-        let query = "
-            SELECT id, user_id, domain_name, is_verified, verification_token, created_at, updated_at 
-            FROM domains 
-            ORDER BY created_at DESC";
+        let query = format!(
+            "SELECT {} FROM domains ORDER BY created_at DESC",
+            DOMAIN_ENTRY_COLUMNS
+        );
 
         let query = tiberius::Query::new(query);
         let stream = query.query(&mut *conn).await?;
         let rows = stream.into_first_result().await?;
 
-        let mut domains = Vec::new();
-        for row in rows {
-            let id: i64 = row.get(0).unwrap();
-            let user_id: Option<i64> = row.get(1);
-            let domain_name: &str = row.get(2).unwrap();
-            let is_verified: bool = row.get(3).unwrap();
-            let verification_token: Option<&str> = row.get(4);
-            let created_at: chrono::DateTime<chrono::Utc> = row.get(5).unwrap();
-            let updated_at: chrono::DateTime<chrono::Utc> = row.get(6).unwrap();
-
-            domains.push(DomainEntry {
-                id,
-                user_id,
-                domain_name: domain_name.to_string(),
-                is_verified,
-                verification_token: verification_token.map(|s| s.to_string()),
-                created_at,
-                updated_at,
-            });
-        }
-
-        Ok(domains)
+        Ok(rows.iter().map(domain_entry_from_row).collect())
     }
 
     pub async fn update_domain_verification_by_id(
@@ -525,7 +1003,140 @@ impl DatabaseService {
         query.bind(is_verified);
 
         let result = query.execute(&mut *conn).await?;
-        Ok(result.rows_affected().len() > 0)
+        Ok(!result.rows_affected().is_empty())
+    }
+
+    // Make a verified domain the only default among its owner's domains; user_id None switches the
+    // default among the shared domains that have no owner
+    pub async fn set_default_domain(
+        pool: &DatabasePool,
+        domain_id: i64,
+        user_id: Option<i64>,
+    ) -> Result<bool> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        begin_transaction(&mut conn).await?;
+
+        let result = async {
+            let query = "
+                DECLARE @resource NVARCHAR(255) = CONCAT(N'domains.default.', @P2);
+                EXEC sp_getapplock @Resource = @resource, @LockMode = 'Exclusive', @LockOwner = 'Transaction';
+                UPDATE domains
+                SET is_default = CASE WHEN id = @P1 THEN 1 ELSE 0 END, updated_at = GETUTCDATE()
+                WHERE (user_id = @P2 OR (@P2 IS NULL AND user_id IS NULL))
+                  AND EXISTS (
+                      SELECT 1 FROM domains
+                      WHERE id = @P1 AND is_verified = 1
+                        AND (user_id = @P2 OR (@P2 IS NULL AND user_id IS NULL)))";
+
+            let mut query = tiberius::Query::new(query);
+            query.bind(domain_id);
+            query.bind(user_id);
+
+            let result = query.execute(&mut *conn).await?;
+            Ok(result.total() > 0)
+        }
+        .await;
+
+        finish_transaction(&mut conn, result).await
+    }
+
+    // Replace the verification tokens of the given domains and mark them unverified, in batches within
+    // a single transaction that also records an audit log entry
+    pub async fn regenerate_domain_tokens(
+        pool: &DatabasePool,
+        new_tokens: &[(i64, String)],
+        actor_user_id: i64,
+    ) -> Result<u64> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        begin_transaction(&mut conn).await?;
+
+        let result = async {
+            let mut updated = 0;
+            for batch in new_tokens.chunks(BULK_UPDATE_BATCH_SIZE) {
+                let values = (0..batch.len())
+                    .map(|i| format!("(@P{}, @P{})", i * 2 + 1, i * 2 + 2))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let query = format!(
+                    "UPDATE d
+                    SET verification_token = v.token, is_verified = 0, updated_at = GETUTCDATE()
+                    FROM domains d
+                    JOIN (VALUES {}) AS v(id, token) ON d.id = v.id",
+                    values
+                );
+
+                let mut query = tiberius::Query::new(query);
+                for (domain_id, token) in batch {
+                    query.bind(*domain_id);
+                    query.bind(token.as_str());
+                }
+
+                updated += query.execute(&mut *conn).await?.total();
+            }
+
+            let domain_ids: Vec<i64> = new_tokens.iter().map(|(id, _)| *id).collect();
+            insert_audit_log_entry(
+                &mut conn,
+                Some(actor_user_id),
+                "domains.regenerate_tokens",
+                &serde_json::json!({ "domain_ids": domain_ids, "updated": updated }),
+            )
+            .await?;
+
+            Ok(updated)
+        }
+        .await;
+
+        finish_transaction(&mut conn, result).await
+    }
+
+    // Change a user's rate limit tier and record the change in the audit log, in one transaction.
+    // Returns false when no user has the given ID.
+    pub async fn update_user_rate_limit_tier(
+        pool: &DatabasePool,
+        user_id: i64,
+        rate_limit_tier: &str,
+        actor_user_id: i64,
+    ) -> Result<bool> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+
+        begin_transaction(&mut conn).await?;
+
+        let result = async {
+            let mut query = tiberius::Query::new(
+                "UPDATE users SET rate_limit_tier = @P2, updated_at = GETUTCDATE() WHERE id = @P1",
+            );
+            query.bind(user_id);
+            query.bind(rate_limit_tier);
+
+            if query.execute(&mut *conn).await?.total() == 0 {
+                return Ok(false);
+            }
+
+            insert_audit_log_entry(
+                &mut conn,
+                Some(actor_user_id),
+                "users.set_rate_limit_tier",
+                &serde_json::json!({ "user_id": user_id, "rate_limit_tier": rate_limit_tier }),
+            )
+            .await?;
+
+            Ok(true)
+        }
+        .await;
+
+        finish_transaction(&mut conn, result).await
     }
 
     // User management methods
@@ -573,7 +1184,7 @@ impl DatabaseService {
             .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
 
         let query = "
-            SELECT id, username, email, passkey_public_key, passkey_credential_id, passkey_counter, created_at, updated_at
+            SELECT id, username, email, passkey_public_key, passkey_credential_id, passkey_counter, created_at, updated_at, rate_limit_tier
             FROM users 
             WHERE id = @P1";
 
@@ -592,6 +1203,7 @@ impl DatabaseService {
             let passkey_counter: i64 = row.get(5).unwrap();
             let created_at: DateTime<Utc> = row.get(6).unwrap();
             let updated_at: DateTime<Utc> = row.get(7).unwrap();
+            let rate_limit_tier: &str = row.get(8).unwrap();
 
             Ok(Some(UserEntry {
                 id,
@@ -602,6 +1214,7 @@ impl DatabaseService {
                 passkey_counter: passkey_counter as u32,
                 created_at,
                 updated_at,
+                rate_limit_tier: rate_limit_tier.to_string(),
             }))
         } else {
             Ok(None)
@@ -618,7 +1231,7 @@ impl DatabaseService {
             .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
 
         let query = "
-            SELECT id, username, email, passkey_public_key, passkey_credential_id, passkey_counter, created_at, updated_at
+            SELECT id, username, email, passkey_public_key, passkey_credential_id, passkey_counter, created_at, updated_at, rate_limit_tier
             FROM users 
             WHERE username = @P1";
 
@@ -637,6 +1250,7 @@ impl DatabaseService {
             let passkey_counter: i64 = row.get(5).unwrap();
             let created_at: DateTime<Utc> = row.get(6).unwrap();
             let updated_at: DateTime<Utc> = row.get(7).unwrap();
+            let rate_limit_tier: &str = row.get(8).unwrap();
 
             Ok(Some(UserEntry {
                 id,
@@ -647,6 +1261,7 @@ impl DatabaseService {
                 passkey_counter: passkey_counter as u32,
                 created_at,
                 updated_at,
+                rate_limit_tier: rate_limit_tier.to_string(),
             }))
         } else {
             Ok(None)
@@ -663,7 +1278,7 @@ impl DatabaseService {
             .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
 
         let query = "
-            SELECT id, username, email, passkey_public_key, passkey_credential_id, passkey_counter, created_at, updated_at
+            SELECT id, username, email, passkey_public_key, passkey_credential_id, passkey_counter, created_at, updated_at, rate_limit_tier
             FROM users 
             WHERE email = @P1";
 
@@ -682,6 +1297,7 @@ impl DatabaseService {
             let passkey_counter: i64 = row.get(5).unwrap();
             let created_at: DateTime<Utc> = row.get(6).unwrap();
             let updated_at: DateTime<Utc> = row.get(7).unwrap();
+            let rate_limit_tier: &str = row.get(8).unwrap();
 
             Ok(Some(UserEntry {
                 id,
@@ -692,6 +1308,7 @@ impl DatabaseService {
                 passkey_counter: passkey_counter as u32,
                 created_at,
                 updated_at,
+                rate_limit_tier: rate_limit_tier.to_string(),
             }))
         } else {
             Ok(None)
@@ -718,6 +1335,109 @@ impl DatabaseService {
         query.bind(new_counter as i64);
 
         let result = query.execute(&mut *conn).await?;
-        Ok(result.rows_affected().len() > 0)
+        Ok(!result.rows_affected().is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_update_set_clause_resets_media_type_with_new_target() {
+        let update = UrlUpdate {
+            original_url: Some("https://example.com/new".to_string()),
+            expiry_grace_seconds: Some(None),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            url_update_set_clause(&update),
+            "original_url = @P2, expiry_grace_seconds = @P3, media_type = NULL, updated_at = GETUTCDATE()"
+        );
+    }
+
+    #[test]
+    fn test_url_update_set_clause_keeps_media_type_for_same_target() {
+        let update = UrlUpdate {
+            expires_at: Some(None),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            url_update_set_clause(&update),
+            "expires_at = @P2, updated_at = GETUTCDATE()"
+        );
+    }
+
+    // Requires a migrated database: DATABASE_URL=... cargo test -- --ignored
+    #[actix_rt::test]
+    #[ignore]
+    async fn test_concurrent_set_default_leaves_exactly_one_default() {
+        let config = DatabaseConfig::from_env(&Settings::from_env()).expect("DATABASE_URL must be set");
+        let pool = create_connection_pool(&config).await.unwrap();
+
+        let suffix = Utc::now().timestamp_nanos_opt().unwrap();
+        let username = format!("default-test-{}", suffix);
+        let user_id = DatabaseService::create_user(
+            &pool,
+            &username,
+            &format!("{}@example.com", username),
+            b"public-key",
+            username.as_bytes(),
+            0,
+        )
+        .await
+        .unwrap();
+
+        let first = format!("first-{}.example.com", suffix);
+        let second = format!("second-{}.example.com", suffix);
+        // Cleanup below runs even when a step fails, so failures are returned rather than panicking
+        let outcome: Result<(bool, bool, usize, bool)> = async {
+            let first_id =
+                DatabaseService::insert_domain(&pool, &first, Some(user_id), true, None).await?;
+            let second_id =
+                DatabaseService::insert_domain(&pool, &second, Some(user_id), true, None).await?;
+
+            let (first_result, second_result) = tokio::join!(
+                DatabaseService::set_default_domain(&pool, first_id, Some(user_id)),
+                DatabaseService::set_default_domain(&pool, second_id, Some(user_id))
+            );
+
+            let mut defaults = 0;
+            for id in [first_id, second_id] {
+                let domain = DatabaseService::get_domain_by_id(&pool, id).await?;
+                if domain.is_some_and(|domain| domain.is_default) {
+                    defaults += 1;
+                }
+            }
+
+            // UX_domains_user_id_default rejects a second default for the same owner
+            let mut conn = pool
+                .get()
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))?;
+            let mut second_default =
+                tiberius::Query::new("UPDATE domains SET is_default = 1 WHERE user_id = @P1");
+            second_default.bind(user_id);
+            let second_default_rejected = second_default.execute(&mut *conn).await.is_err();
+
+            Ok((first_result?, second_result?, defaults, second_default_rejected))
+        }
+        .await;
+
+        let mut conn = pool.get().await.unwrap();
+        let mut cleanup = tiberius::Query::new(
+            "DELETE FROM domains WHERE user_id = @P1; DELETE FROM users WHERE id = @P1",
+        );
+        cleanup.bind(user_id);
+        cleanup.execute(&mut *conn).await.unwrap();
+
+        let (first_switched, second_switched, defaults, second_default_rejected) =
+            outcome.unwrap();
+        assert!(first_switched);
+        assert!(second_switched);
+        assert_eq!(defaults, 1);
+        assert!(second_default_rejected);
     }
 }
@@ -0,0 +1,643 @@
+use crate::config::Settings;
+use crate::database::DomainEntry;
+
+// How the default domain is chosen for a new short link when the request does not name one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefaultDomainStrategy {
+    // Earliest added verified domain
+    Oldest,
+    // Most recently added verified domain
+    Newest,
+    // The domain flagged is_default, falling back to the oldest when none is flagged
+    ExplicitDefaultFlag,
+    // First verified domain by name
+    Alphabetical,
+}
+
+impl DefaultDomainStrategy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "oldest" => Some(DefaultDomainStrategy::Oldest),
+            "newest" => Some(DefaultDomainStrategy::Newest),
+            "explicit-default-flag" => Some(DefaultDomainStrategy::ExplicitDefaultFlag),
+            "alphabetical" => Some(DefaultDomainStrategy::Alphabetical),
+            _ => None,
+        }
+    }
+
+    // Read DEFAULT_DOMAIN_STRATEGY; unset keeps the historical "newest" behaviour
+    pub fn from_env(settings: &Settings) -> anyhow::Result<Self> {
+        match settings.get("DEFAULT_DOMAIN_STRATEGY") {
+            Some(value) => Self::parse(&value).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid DEFAULT_DOMAIN_STRATEGY '{}'. Expected one of: oldest, newest, explicit-default-flag, alphabetical",
+                    value
+                )
+            }),
+            None => Ok(DefaultDomainStrategy::Newest),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DefaultDomainStrategy::Oldest => "oldest",
+            DefaultDomainStrategy::Newest => "newest",
+            DefaultDomainStrategy::ExplicitDefaultFlag => "explicit-default-flag",
+            DefaultDomainStrategy::Alphabetical => "alphabetical",
+        }
+    }
+
+    // Pick the default from a set of verified domains, independent of the order they were loaded in.
+    // Ties on created_at are broken by id so the choice is stable.
+    pub fn select<'a>(&self, domains: &'a [DomainEntry]) -> Option<&'a DomainEntry> {
+        let oldest = || {
+            domains
+                .iter()
+                .min_by_key(|domain| (domain.created_at, domain.id))
+        };
+
+        match self {
+            DefaultDomainStrategy::Oldest => oldest(),
+            DefaultDomainStrategy::Newest => domains
+                .iter()
+                .max_by_key(|domain| (domain.created_at, domain.id)),
+            DefaultDomainStrategy::ExplicitDefaultFlag => domains
+                .iter()
+                .filter(|domain| domain.is_default)
+                .min_by_key(|domain| (domain.created_at, domain.id))
+                .or_else(oldest),
+            DefaultDomainStrategy::Alphabetical => domains
+                .iter()
+                .min_by(|a, b| a.domain_name.cmp(&b.domain_name)),
+        }
+    }
+}
+
+// Why a base URL was chosen for a new short link
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BaseUrlReason {
+    // The request named a verified domain
+    Requested,
+    // The domain flagged is_default under the explicit-default-flag strategy
+    Default,
+    // The first verified domain under the configured ordering strategy
+    FirstVerified,
+    // No verified domains; the server's own address in development mode
+    DevFallback,
+}
+
+impl BaseUrlReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BaseUrlReason::Requested => "requested",
+            BaseUrlReason::Default => "default",
+            BaseUrlReason::FirstVerified => "first-verified",
+            BaseUrlReason::DevFallback => "dev-fallback",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedBaseUrl {
+    pub base_url: String,
+    // None when the development fallback was used
+    pub domain_name: Option<String>,
+    pub reason: BaseUrlReason,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BaseUrlError {
+    RequestedDomainUnavailable(String),
+    NoVerifiedDomains,
+}
+
+impl std::fmt::Display for BaseUrlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BaseUrlError::RequestedDomainUnavailable(domain) => {
+                write!(f, "Domain '{}' is not verified or does not exist", domain)
+            }
+            BaseUrlError::NoVerifiedDomains => write!(
+                f,
+                "No verified domains available for URL shortening. Please add and verify a custom domain first."
+            ),
+        }
+    }
+}
+
+// Normalize user input into a bare domain name: drops a pasted scheme, path, port and trailing
+// dot, lowercases, and converts internationalized names to punycode. Domains are stored in this
+// form, so requested domains are normalized the same way before they are matched.
+pub fn normalize_domain(input: &str) -> String {
+    let mut domain = input.trim();
+
+    // Strip a scheme such as "https://" if the user pasted a full URL
+    if let Some(index) = domain.find("://") {
+        domain = &domain[index + 3..];
+    }
+
+    // Strip any path, query or fragment (this also removes trailing slashes)
+    if let Some(index) = domain.find(['/', '?', '#']) {
+        domain = &domain[..index];
+    }
+
+    // Strip credentials and port
+    if let Some(index) = domain.rfind('@') {
+        domain = &domain[index + 1..];
+    }
+    if let Some(index) = domain.rfind(':') {
+        if domain[index + 1..].chars().all(|c| c.is_ascii_digit()) {
+            domain = &domain[..index];
+        }
+    }
+
+    let domain = domain.trim_end_matches('.');
+
+    // Lowercase and convert to punycode; leave invalid input for format validation to reject
+    match url::Host::parse(domain) {
+        Ok(url::Host::Domain(ascii)) => ascii,
+        _ => domain.to_lowercase(),
+    }
+}
+
+// Whether a caller may issue links on a domain: their own domains, and shared domains that have no
+// owner (such as those added before domains recorded one)
+pub fn is_usable_by(domain: &DomainEntry, user_id: Option<i64>) -> bool {
+    domain.user_id.is_none() || domain.user_id == user_id
+}
+
+// The domains a caller's default is chosen from: their own verified domains, or the shared domains
+// when they have none. Other users' domains are never candidates.
+pub fn default_candidates(domains: &[DomainEntry], user_id: Option<i64>) -> Vec<DomainEntry> {
+    let own: Vec<DomainEntry> = domains
+        .iter()
+        .filter(|domain| user_id.is_some() && domain.user_id == user_id)
+        .cloned()
+        .collect();
+    if !own.is_empty() {
+        return own;
+    }
+
+    domains
+        .iter()
+        .filter(|domain| domain.user_id.is_none())
+        .cloned()
+        .collect()
+}
+
+// Settings and request details that base URL resolution depends on
+pub struct BaseUrlContext<'a> {
+    // The signed-in caller, or None for anonymous requests
+    pub user_id: Option<i64>,
+    pub short_link_scheme: &'a str,
+    pub strategy: DefaultDomainStrategy,
+    // SKIP_DOMAIN_VERIFICATION: allow the development fallback when no domain is verified
+    pub allow_dev_fallback: bool,
+    // Scheme and host the request arrived on, used by the development fallback
+    pub connection_scheme: &'a str,
+    pub connection_host: &'a str,
+}
+
+// Choose the base URL for a new short link: the requested domain, else the configured default among
+// the caller's candidates, else (in development) the server's own address
+pub fn resolve_base_url(
+    domains: &[DomainEntry],
+    requested_domain: Option<&str>,
+    context: &BaseUrlContext,
+) -> Result<ResolvedBaseUrl, BaseUrlError> {
+    // A blank domain (e.g. "?domain=") means none was requested
+    let requested_domain = requested_domain
+        .map(str::trim)
+        .filter(|domain| !domain.is_empty());
+
+    if let Some(requested_domain) = requested_domain {
+        let normalized = normalize_domain(requested_domain);
+        return match domains.iter().find(|d| {
+            normalize_domain(&d.domain_name) == normalized && is_usable_by(d, context.user_id)
+        }) {
+            Some(domain) => Ok(ResolvedBaseUrl {
+                base_url: format!("{}://{}", context.short_link_scheme, domain.domain_name),
+                domain_name: Some(domain.domain_name.clone()),
+                reason: BaseUrlReason::Requested,
+            }),
+            None => Err(BaseUrlError::RequestedDomainUnavailable(
+                requested_domain.to_string(),
+            )),
+        };
+    }
+
+    let candidates = default_candidates(domains, context.user_id);
+    if let Some(domain) = context.strategy.select(&candidates) {
+        let reason = if context.strategy == DefaultDomainStrategy::ExplicitDefaultFlag
+            && domain.is_default
+        {
+            BaseUrlReason::Default
+        } else {
+            BaseUrlReason::FirstVerified
+        };
+        return Ok(ResolvedBaseUrl {
+            base_url: format!("{}://{}", context.short_link_scheme, domain.domain_name),
+            domain_name: Some(domain.domain_name.clone()),
+            reason,
+        });
+    }
+
+    if !context.allow_dev_fallback {
+        return Err(BaseUrlError::NoVerifiedDomains);
+    }
+
+    // Fall back to localhost:8080 if connection info is not reliable
+    let base_url = if context.connection_host.is_empty() || context.connection_scheme.is_empty() {
+        "http://localhost:8080".to_string()
+    } else {
+        format!("{}://{}", context.connection_scheme, context.connection_host)
+    };
+
+    Ok(ResolvedBaseUrl {
+        base_url,
+        domain_name: None,
+        reason: BaseUrlReason::DevFallback,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn domain(id: i64, domain_name: &str, day: u32, is_default: bool) -> DomainEntry {
+        let created_at = Utc.with_ymd_and_hms(2025, 1, day, 0, 0, 0).unwrap();
+        DomainEntry {
+            id,
+            user_id: None,
+            domain_name: domain_name.to_string(),
+            is_verified: true,
+            is_default,
+            verification_token: None,
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    // Loaded newest-first, as get_verified_domains returns them
+    fn fixed_domains() -> Vec<DomainEntry> {
+        vec![
+            domain(3, "beta.example.com", 3, false),
+            domain(2, "gamma.example.com", 2, true),
+            domain(1, "delta.example.com", 1, false),
+            domain(4, "alpha.example.com", 1, false),
+        ]
+    }
+
+    #[test]
+    fn test_oldest_strategy() {
+        let domains = fixed_domains();
+        let selected = DefaultDomainStrategy::Oldest.select(&domains).unwrap();
+        assert_eq!(selected.domain_name, "delta.example.com");
+    }
+
+    #[test]
+    fn test_newest_strategy() {
+        let domains = fixed_domains();
+        let selected = DefaultDomainStrategy::Newest.select(&domains).unwrap();
+        assert_eq!(selected.domain_name, "beta.example.com");
+    }
+
+    #[test]
+    fn test_explicit_default_flag_strategy() {
+        let domains = fixed_domains();
+        let selected = DefaultDomainStrategy::ExplicitDefaultFlag
+            .select(&domains)
+            .unwrap();
+        assert_eq!(selected.domain_name, "gamma.example.com");
+    }
+
+    #[test]
+    fn test_explicit_default_flag_strategy_falls_back_to_oldest() {
+        let mut domains = fixed_domains();
+        for domain in &mut domains {
+            domain.is_default = false;
+        }
+        let selected = DefaultDomainStrategy::ExplicitDefaultFlag
+            .select(&domains)
+            .unwrap();
+        assert_eq!(selected.domain_name, "delta.example.com");
+    }
+
+    #[test]
+    fn test_alphabetical_strategy() {
+        let domains = fixed_domains();
+        let selected = DefaultDomainStrategy::Alphabetical
+            .select(&domains)
+            .unwrap();
+        assert_eq!(selected.domain_name, "alpha.example.com");
+    }
+
+    #[test]
+    fn test_strategies_with_no_domains() {
+        for strategy in [
+            DefaultDomainStrategy::Oldest,
+            DefaultDomainStrategy::Newest,
+            DefaultDomainStrategy::ExplicitDefaultFlag,
+            DefaultDomainStrategy::Alphabetical,
+        ] {
+            assert!(strategy.select(&[]).is_none());
+        }
+    }
+
+    fn context(strategy: DefaultDomainStrategy, allow_dev_fallback: bool) -> BaseUrlContext<'static> {
+        BaseUrlContext {
+            user_id: None,
+            short_link_scheme: "https",
+            strategy,
+            allow_dev_fallback,
+            connection_scheme: "http",
+            connection_host: "127.0.0.1:8080",
+        }
+    }
+
+    #[test]
+    fn test_resolve_base_url_requested_domain() {
+        let domains = fixed_domains();
+        let resolved = resolve_base_url(
+            &domains,
+            Some("beta.example.com"),
+            &context(DefaultDomainStrategy::Oldest, false),
+        )
+        .unwrap();
+
+        assert_eq!(resolved.base_url, "https://beta.example.com");
+        assert_eq!(resolved.domain_name.as_deref(), Some("beta.example.com"));
+        assert_eq!(resolved.reason, BaseUrlReason::Requested);
+    }
+
+    #[test]
+    fn test_resolve_base_url_blank_or_padded_requested_domain() {
+        let domains = fixed_domains();
+        let context = context(DefaultDomainStrategy::Oldest, false);
+
+        let padded = resolve_base_url(&domains, Some("  beta.example.com "), &context).unwrap();
+        assert_eq!(padded.base_url, "https://beta.example.com");
+        assert_eq!(padded.reason, BaseUrlReason::Requested);
+
+        for blank in ["", "   "] {
+            let resolved = resolve_base_url(&domains, Some(blank), &context).unwrap();
+            assert_eq!(resolved.base_url, "https://delta.example.com");
+            assert_eq!(resolved.reason, BaseUrlReason::FirstVerified);
+        }
+    }
+
+    #[test]
+    fn test_resolve_base_url_normalizes_requested_domain() {
+        let mut domains = fixed_domains();
+        domains.push(domain(5, "xn--bcher-kva.example", 4, false));
+        let context = context(DefaultDomainStrategy::Oldest, false);
+
+        for (requested, expected) in [
+            ("Beta.Example.COM", "beta.example.com"),
+            ("https://beta.example.com/", "beta.example.com"),
+            ("beta.example.com:443", "beta.example.com"),
+            ("Bücher.example", "xn--bcher-kva.example"),
+        ] {
+            let resolved = resolve_base_url(&domains, Some(requested), &context).unwrap();
+            assert_eq!(
+                resolved.domain_name.as_deref(),
+                Some(expected),
+                "{}",
+                requested
+            );
+            assert_eq!(resolved.reason, BaseUrlReason::Requested);
+        }
+    }
+
+    #[test]
+    fn test_normalize_domain() {
+        assert_eq!(normalize_domain("example.com"), "example.com");
+        assert_eq!(normalize_domain("  Example.COM  "), "example.com");
+        assert_eq!(normalize_domain("HTTPS://Example.com/"), "example.com");
+        assert_eq!(normalize_domain("https://example.com///"), "example.com");
+        assert_eq!(
+            normalize_domain("http://sub.example.com/path?q=1#top"),
+            "sub.example.com"
+        );
+        assert_eq!(
+            normalize_domain("https://user@example.com:8443/"),
+            "example.com"
+        );
+        assert_eq!(normalize_domain("example.com."), "example.com");
+        assert_eq!(normalize_domain("Bücher.example"), "xn--bcher-kva.example");
+        assert_eq!(normalize_domain(""), "");
+    }
+
+    #[test]
+    fn test_resolve_base_url_unverified_requested_domain() {
+        let domains = fixed_domains();
+        let result = resolve_base_url(
+            &domains,
+            Some("unknown.example.com"),
+            &context(DefaultDomainStrategy::Oldest, true),
+        );
+
+        assert_eq!(
+            result,
+            Err(BaseUrlError::RequestedDomainUnavailable(
+                "unknown.example.com".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_resolve_base_url_default_flag() {
+        let domains = fixed_domains();
+        let resolved = resolve_base_url(
+            &domains,
+            None,
+            &context(DefaultDomainStrategy::ExplicitDefaultFlag, false),
+        )
+        .unwrap();
+
+        assert_eq!(resolved.base_url, "https://gamma.example.com");
+        assert_eq!(resolved.reason, BaseUrlReason::Default);
+    }
+
+    #[test]
+    fn test_resolve_base_url_first_verified() {
+        let domains = fixed_domains();
+        let resolved = resolve_base_url(
+            &domains,
+            None,
+            &context(DefaultDomainStrategy::Newest, false),
+        )
+        .unwrap();
+
+        assert_eq!(resolved.base_url, "https://beta.example.com");
+        assert_eq!(resolved.reason, BaseUrlReason::FirstVerified);
+    }
+
+    #[test]
+    fn test_resolve_base_url_uses_short_link_scheme() {
+        let domains = fixed_domains();
+        let mut context = context(DefaultDomainStrategy::Alphabetical, false);
+        context.short_link_scheme = "http";
+
+        let resolved = resolve_base_url(&domains, None, &context).unwrap();
+
+        assert_eq!(resolved.base_url, "http://alpha.example.com");
+    }
+
+    #[test]
+    fn test_resolve_base_url_dev_fallback() {
+        let resolved =
+            resolve_base_url(&[], None, &context(DefaultDomainStrategy::Newest, true)).unwrap();
+
+        assert_eq!(resolved.base_url, "http://127.0.0.1:8080");
+        assert_eq!(resolved.domain_name, None);
+        assert_eq!(resolved.reason, BaseUrlReason::DevFallback);
+
+        let mut context = context(DefaultDomainStrategy::Newest, true);
+        context.connection_host = "";
+        let resolved = resolve_base_url(&[], None, &context).unwrap();
+        assert_eq!(resolved.base_url, "http://localhost:8080");
+    }
+
+    #[test]
+    fn test_resolve_base_url_without_domains_in_production() {
+        let result = resolve_base_url(&[], None, &context(DefaultDomainStrategy::Newest, false));
+
+        assert_eq!(result, Err(BaseUrlError::NoVerifiedDomains));
+    }
+
+    fn owned(mut domain: DomainEntry, user_id: i64) -> DomainEntry {
+        domain.user_id = Some(user_id);
+        domain
+    }
+
+    // User 1 owns beta and delta, user 2 owns gamma; alpha has no owner and is shared
+    fn multi_tenant_domains() -> Vec<DomainEntry> {
+        vec![
+            owned(domain(3, "beta.example.com", 3, false), 1),
+            owned(domain(2, "gamma.example.com", 2, false), 2),
+            owned(domain(1, "delta.example.com", 1, false), 1),
+            domain(4, "alpha.example.com", 1, false),
+        ]
+    }
+
+    #[test]
+    fn test_strategies_only_consider_callers_domains() {
+        let domains = multi_tenant_domains();
+        let for_user = |strategy: DefaultDomainStrategy, user_id: i64| {
+            let mut context = context(strategy, false);
+            context.user_id = Some(user_id);
+            resolve_base_url(&domains, None, &context).unwrap().base_url
+        };
+
+        assert_eq!(
+            for_user(DefaultDomainStrategy::Newest, 1),
+            "https://beta.example.com"
+        );
+        assert_eq!(
+            for_user(DefaultDomainStrategy::Oldest, 1),
+            "https://delta.example.com"
+        );
+        assert_eq!(
+            for_user(DefaultDomainStrategy::Alphabetical, 1),
+            "https://beta.example.com"
+        );
+        for strategy in [
+            DefaultDomainStrategy::Oldest,
+            DefaultDomainStrategy::Newest,
+            DefaultDomainStrategy::Alphabetical,
+        ] {
+            assert_eq!(for_user(strategy, 2), "https://gamma.example.com");
+        }
+    }
+
+    #[test]
+    fn test_shared_domains_when_caller_has_none() {
+        let domains = multi_tenant_domains();
+
+        let mut context = context(DefaultDomainStrategy::Newest, false);
+        context.user_id = Some(3);
+        let resolved = resolve_base_url(&domains, None, &context).unwrap();
+        assert_eq!(resolved.base_url, "https://alpha.example.com");
+
+        context.user_id = None;
+        let resolved = resolve_base_url(&domains, None, &context).unwrap();
+        assert_eq!(resolved.base_url, "https://alpha.example.com");
+    }
+
+    #[test]
+    fn test_default_flag_is_per_user() {
+        // Each user and the shared domains keep their own default
+        let mut domains = multi_tenant_domains();
+        domains[0].is_default = true; // beta, user 1
+        domains[1].is_default = true; // gamma, user 2
+        domains[3].is_default = true; // alpha, shared
+        domains.push(owned(domain(5, "epsilon.example.com", 4, false), 2));
+        domains.push(domain(6, "zeta.example.com", 5, false));
+
+        let resolve = |user_id: Option<i64>| {
+            let mut context = context(DefaultDomainStrategy::ExplicitDefaultFlag, false);
+            context.user_id = user_id;
+            resolve_base_url(&domains, None, &context).unwrap()
+        };
+
+        assert_eq!(resolve(Some(1)).base_url, "https://beta.example.com");
+        assert_eq!(resolve(Some(2)).base_url, "https://gamma.example.com");
+        assert_eq!(resolve(Some(3)).base_url, "https://alpha.example.com");
+        assert_eq!(resolve(None).base_url, "https://alpha.example.com");
+        assert_eq!(resolve(Some(2)).reason, BaseUrlReason::Default);
+    }
+
+    #[test]
+    fn test_other_users_default_flag_is_ignored() {
+        // User 2 flagged gamma, but user 1 has no flagged domain of their own
+        let mut domains = multi_tenant_domains();
+        domains[1].is_default = true;
+
+        let mut context = context(DefaultDomainStrategy::ExplicitDefaultFlag, false);
+        context.user_id = Some(1);
+        let resolved = resolve_base_url(&domains, None, &context).unwrap();
+
+        assert_eq!(resolved.base_url, "https://delta.example.com");
+        assert_eq!(resolved.reason, BaseUrlReason::FirstVerified);
+    }
+
+    #[test]
+    fn test_requested_domain_of_another_user_is_unavailable() {
+        let domains = multi_tenant_domains();
+        let mut context = context(DefaultDomainStrategy::Newest, false);
+        context.user_id = Some(1);
+
+        assert_eq!(
+            resolve_base_url(&domains, Some("gamma.example.com"), &context),
+            Err(BaseUrlError::RequestedDomainUnavailable(
+                "gamma.example.com".to_string()
+            ))
+        );
+        // Shared domains can still be requested by name
+        assert!(resolve_base_url(&domains, Some("alpha.example.com"), &context).is_ok());
+    }
+
+    #[test]
+    fn test_parse_strategy() {
+        assert_eq!(
+            DefaultDomainStrategy::parse("oldest"),
+            Some(DefaultDomainStrategy::Oldest)
+        );
+        assert_eq!(
+            DefaultDomainStrategy::parse(" Newest "),
+            Some(DefaultDomainStrategy::Newest)
+        );
+        assert_eq!(
+            DefaultDomainStrategy::parse("explicit-default-flag"),
+            Some(DefaultDomainStrategy::ExplicitDefaultFlag)
+        );
+        assert_eq!(
+            DefaultDomainStrategy::parse("ALPHABETICAL"),
+            Some(DefaultDomainStrategy::Alphabetical)
+        );
+        assert_eq!(DefaultDomainStrategy::parse("random"), None);
+        assert_eq!(DefaultDomainStrategy::parse(""), None);
+    }
+}
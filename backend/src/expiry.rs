@@ -0,0 +1,253 @@
+use crate::config::Settings;
+use crate::database::UrlEntry;
+use crate::export::escape_html;
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+
+// Where an expiring link is in its lifetime
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExpiryState {
+    // No expiry set, or not yet expired: redirect as normal
+    Active,
+    // Expired but within the grace window: show the renewal page
+    InGrace,
+    // Past the grace window: treat the link as gone
+    Expired,
+}
+
+impl ExpiryState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExpiryState::Active => "active",
+            ExpiryState::InGrace => "in-grace",
+            ExpiryState::Expired => "expired",
+        }
+    }
+}
+
+// Global expiry settings; per-link values on urls.expiry_grace_seconds take precedence
+#[derive(Debug, Clone, Copy)]
+pub struct ExpiryConfig {
+    pub grace_period: Duration,
+}
+
+impl ExpiryConfig {
+    // Read EXPIRED_LINK_GRACE_SECONDS; unset keeps expired links gone immediately
+    pub fn from_env(settings: &Settings) -> anyhow::Result<Self> {
+        let grace_seconds = match settings.get("EXPIRED_LINK_GRACE_SECONDS") {
+            Some(value) => value
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .filter(|seconds| *seconds >= 0)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Invalid EXPIRED_LINK_GRACE_SECONDS '{}'. Expected a non-negative number of seconds",
+                        value
+                    )
+                })?,
+            None => 0,
+        };
+
+        info!("Expired link grace period: {}s", grace_seconds);
+
+        Ok(ExpiryConfig {
+            grace_period: Duration::seconds(grace_seconds),
+        })
+    }
+
+    // The grace window that applies to a link
+    pub fn grace_period_for(&self, entry: &UrlEntry) -> Duration {
+        entry
+            .expiry_grace_seconds
+            .map(Duration::seconds)
+            .unwrap_or(self.grace_period)
+    }
+
+    pub fn state_of(&self, entry: &UrlEntry, now: DateTime<Utc>) -> ExpiryState {
+        expiry_state(entry.expires_at, self.grace_period_for(entry), now)
+    }
+}
+
+pub fn expiry_state(
+    expires_at: Option<DateTime<Utc>>,
+    grace_period: Duration,
+    now: DateTime<Utc>,
+) -> ExpiryState {
+    match expires_at {
+        None => ExpiryState::Active,
+        Some(expires_at) if now < expires_at => ExpiryState::Active,
+        Some(expires_at) if now < expires_at + grace_period => ExpiryState::InGrace,
+        Some(_) => ExpiryState::Expired,
+    }
+}
+
+// Page served for a link inside its grace window. The owner gets a form that renews the link
+// through POST /api/urls/{id}/renew; everyone else is told the owner can renew it.
+pub fn render_grace_page(entry: &UrlEntry, is_owner: bool) -> String {
+    let short_id = escape_html(&entry.shortened_url);
+    let expired_at = entry
+        .expires_at
+        .map(|expires_at| expires_at.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default();
+
+    let action = if is_owner {
+        format!(
+            r#"<form id="renew" data-renew-url="/api/urls/{short_id}/renew">
+      <label>Extend by <input name="days" type="number" min="1" value="30" required> days</label>
+      <button type="submit">Renew link</button>
+    </form>
+    <p id="renew-status" role="status"></p>
+    <script>
+      document.getElementById("renew").addEventListener("submit", async (event) => {{
+        event.preventDefault();
+        const form = event.target;
+        const status = document.getElementById("renew-status");
+        const response = await fetch(form.dataset.renewUrl, {{
+          method: "POST",
+          credentials: "same-origin",
+          headers: {{ "Content-Type": "application/json" }},
+          body: JSON.stringify({{ expires_in_seconds: Number(form.days.value) * 86400 }}),
+        }});
+        if (response.ok) {{
+          window.location.reload();
+        }} else {{
+          status.textContent = "The link could not be renewed.";
+        }}
+      }});
+    </script>"#
+        )
+    } else {
+        "<p>If you own this link, sign in to renew it.</p>".to_string()
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta charset="utf-8">
+    <meta name="robots" content="noindex">
+    <title>Link expired - Thalora</title>
+  </head>
+  <body>
+    <h1>This link has expired</h1>
+    <p>The short link <code>{short_id}</code> expired on {expired_at}. The owner can renew it.</p>
+    {action}
+  </body>
+</html>
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn link(expires_at: Option<DateTime<Utc>>, expiry_grace_seconds: Option<i64>) -> UrlEntry {
+        let created_at = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        UrlEntry {
+            id: 1,
+            user_id: Some(7),
+            original_url: "https://example.com/page".to_string(),
+            shortened_url: "abc12345".to_string(),
+            base_url: Some("https://go.example.com".to_string()),
+            media_type: None,
+            expires_at,
+            expiry_grace_seconds,
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    fn config(grace_seconds: i64) -> ExpiryConfig {
+        ExpiryConfig {
+            grace_period: Duration::seconds(grace_seconds),
+        }
+    }
+
+    #[test]
+    fn test_link_without_expiry_is_active() {
+        let now = Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap();
+
+        assert_eq!(
+            config(3600).state_of(&link(None, None), now),
+            ExpiryState::Active
+        );
+    }
+
+    #[test]
+    fn test_pre_expiry_is_active() {
+        let expires_at = Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap();
+        let entry = link(Some(expires_at), None);
+
+        assert_eq!(
+            config(3600).state_of(&entry, expires_at - Duration::seconds(1)),
+            ExpiryState::Active
+        );
+    }
+
+    #[test]
+    fn test_in_grace_window() {
+        let expires_at = Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap();
+        let entry = link(Some(expires_at), None);
+
+        assert_eq!(
+            config(3600).state_of(&entry, expires_at),
+            ExpiryState::InGrace
+        );
+        assert_eq!(
+            config(3600).state_of(&entry, expires_at + Duration::seconds(3599)),
+            ExpiryState::InGrace
+        );
+    }
+
+    #[test]
+    fn test_post_grace_is_expired() {
+        let expires_at = Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap();
+        let entry = link(Some(expires_at), None);
+
+        assert_eq!(
+            config(3600).state_of(&entry, expires_at + Duration::seconds(3600)),
+            ExpiryState::Expired
+        );
+    }
+
+    #[test]
+    fn test_no_grace_period_expires_immediately() {
+        let expires_at = Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap();
+        let entry = link(Some(expires_at), None);
+
+        assert_eq!(config(0).state_of(&entry, expires_at), ExpiryState::Expired);
+    }
+
+    #[test]
+    fn test_per_link_grace_overrides_global() {
+        let expires_at = Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap();
+        let now = expires_at + Duration::hours(2);
+
+        assert_eq!(
+            config(3600).state_of(&link(Some(expires_at), Some(86400)), now),
+            ExpiryState::InGrace
+        );
+        assert_eq!(
+            config(86400).state_of(&link(Some(expires_at), Some(0)), now),
+            ExpiryState::Expired
+        );
+    }
+
+    #[test]
+    fn test_grace_page_renewal_form_only_for_owner() {
+        let expires_at = Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap();
+        let entry = link(Some(expires_at), None);
+
+        let owner_page = render_grace_page(&entry, true);
+        assert!(owner_page.contains("This link has expired"));
+        assert!(owner_page.contains("2025-06-01 00:00 UTC"));
+        assert!(owner_page.contains("/api/urls/abc12345/renew"));
+
+        let visitor_page = render_grace_page(&entry, false);
+        assert!(visitor_page.contains("The owner can renew it"));
+        assert!(!visitor_page.contains("/renew"));
+    }
+}
@@ -0,0 +1,437 @@
+use crate::config::Settings;
+use crate::database::UrlEntry;
+use hmac::{Hmac, Mac};
+use log::{info, warn};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+// Largest export body accepted by POST /exports/verify. The whole body has to be buffered to hash
+// it, so this sits well above the default 256 KiB payload limit (large accounts export several MiB)
+// while still bounding memory per request.
+pub const MAX_VERIFY_EXPORT_BYTES: usize = 16 * 1024 * 1024;
+
+// Secret used to sign exports and check them on verification, read once at startup
+pub struct ExportSigning {
+    secret: Option<String>,
+}
+
+impl ExportSigning {
+    // Read EXPORT_SIGNING_SECRET (signed exports are unavailable when unset)
+    pub fn from_env(settings: &Settings) -> Self {
+        let secret = settings
+            .get("EXPORT_SIGNING_SECRET")
+            .filter(|secret| !secret.is_empty());
+
+        if secret.is_some() {
+            info!("Signed exports: enabled");
+        } else {
+            warn!("EXPORT_SIGNING_SECRET is not set; signed exports and export verification are unavailable");
+        }
+
+        ExportSigning { secret }
+    }
+
+    pub fn secret(&self) -> Option<&[u8]> {
+        self.secret.as_deref().map(str::as_bytes)
+    }
+}
+
+// Which address a bookmark points at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BookmarkTarget {
+    ShortUrl,
+    OriginalUrl,
+}
+
+// Build the full short URL for a link, e.g. https://go.example.com/shortened-url/abc12345
+pub fn short_url_for(entry: &UrlEntry) -> Option<String> {
+    entry
+        .base_url
+        .as_ref()
+        .map(|base_url| format!("{}/shortened-url/{}", base_url, entry.shortened_url))
+}
+
+// Escape text for use inside HTML element content and double-quoted attributes
+pub fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// One bookmark line, indented to sit in a folder at the given depth
+fn push_bookmark(html: &mut String, indent: &str, link: &UrlEntry, target: BookmarkTarget) {
+    let short_url = short_url_for(link);
+    // Links without a recorded base URL can only be exported by their target
+    let (href, title) = match (target, short_url) {
+        (BookmarkTarget::ShortUrl, Some(short_url)) => (short_url, link.original_url.clone()),
+        (BookmarkTarget::OriginalUrl, Some(short_url)) => (link.original_url.clone(), short_url),
+        (_, None) => (link.original_url.clone(), link.original_url.clone()),
+    };
+
+    html.push_str(&format!(
+        "{}<DT><A HREF=\"{}\" ADD_DATE=\"{}\">{}</A>\n",
+        indent,
+        escape_html(&href),
+        link.created_at.timestamp(),
+        escape_html(&title)
+    ));
+}
+
+// Render links in the Netscape bookmark file format that browsers import. Inside the Thalora folder
+// each tag gets a subfolder (a link with several tags appears in each) and untagged links sit at
+// the top level. `tags` maps link IDs to their tag names.
+pub fn render_netscape_bookmarks(
+    links: &[UrlEntry],
+    tags: &HashMap<i64, Vec<String>>,
+    target: BookmarkTarget,
+) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+         <!-- This is an automatically generated file.\n     \
+         It will be read and overwritten.\n     \
+         DO NOT EDIT! -->\n\
+         <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+         <TITLE>Bookmarks</TITLE>\n\
+         <H1>Bookmarks</H1>\n\
+         <DL><p>\n",
+    );
+
+    let mut folders: Vec<(&str, Vec<&UrlEntry>)> = Vec::new();
+    let mut untagged = Vec::new();
+    for link in links {
+        let link_tags = tags.get(&link.id).map(Vec::as_slice).unwrap_or_default();
+        if link_tags.is_empty() {
+            untagged.push(link);
+        }
+        for tag in link_tags {
+            match folders.iter_mut().find(|(name, _)| name == tag) {
+                Some((_, folder)) => folder.push(link),
+                None => folders.push((tag, vec![link])),
+            }
+        }
+    }
+    folders.sort_by_key(|(name, _)| name.to_lowercase());
+
+    html.push_str("    <DT><H3>Thalora</H3>\n");
+    html.push_str("    <DL><p>\n");
+    for (name, folder) in folders {
+        html.push_str(&format!("        <DT><H3>{}</H3>\n", escape_html(name)));
+        html.push_str("        <DL><p>\n");
+        for link in folder {
+            push_bookmark(&mut html, "            ", link, target);
+        }
+        html.push_str("        </DL><p>\n");
+    }
+    for link in untagged {
+        push_bookmark(&mut html, "        ", link, target);
+    }
+    html.push_str("    </DL><p>\n");
+    html.push_str("</DL><p>\n");
+
+    html
+}
+
+// One exported link, shared by the CSV and NDJSON formats
+#[derive(Serialize)]
+struct ExportRow<'a> {
+    short_url: Option<String>,
+    original_url: &'a str,
+    media_type: Option<&'a str>,
+    created_at: String,
+}
+
+impl<'a> ExportRow<'a> {
+    fn from_entry(entry: &'a UrlEntry) -> Self {
+        ExportRow {
+            short_url: short_url_for(entry),
+            original_url: &entry.original_url,
+            media_type: entry.media_type.as_deref(),
+            created_at: entry.created_at.to_rfc3339(),
+        }
+    }
+}
+
+// Quote a CSV field when it contains a delimiter, quote or line break
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Render links as CSV with a header row
+pub fn render_csv(links: &[UrlEntry]) -> String {
+    let mut csv = String::from("short_url,original_url,media_type,created_at\n");
+    for link in links {
+        let row = ExportRow::from_entry(link);
+        csv.push_str(&format!(
+            "{},{},{},{}\n",
+            escape_csv(row.short_url.as_deref().unwrap_or("")),
+            escape_csv(row.original_url),
+            escape_csv(row.media_type.unwrap_or("")),
+            escape_csv(&row.created_at)
+        ));
+    }
+    csv
+}
+
+// Render links as newline-delimited JSON, one object per link
+pub fn render_ndjson(links: &[UrlEntry]) -> String {
+    let mut ndjson = String::new();
+    for link in links {
+        // Serializing a struct of strings cannot fail
+        ndjson.push_str(&serde_json::to_string(&ExportRow::from_entry(link)).unwrap());
+        ndjson.push('\n');
+    }
+    ndjson
+}
+
+// Integrity manifest returned alongside an export so the recipient can detect tampering
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportManifest {
+    pub format: String,
+    pub row_count: usize,
+    pub content_sha256: String,
+    pub generated_at: String,
+    pub signature: String,
+}
+
+impl ExportManifest {
+    // The exact bytes covered by the signature, one field per line
+    pub fn signing_input(
+        format: &str,
+        row_count: usize,
+        content_sha256: &str,
+        generated_at: &str,
+    ) -> String {
+        format!(
+            "thalora-export-v1\n{}\n{}\n{}\n{}",
+            format, row_count, content_sha256, generated_at
+        )
+    }
+
+    // Hash the export body and sign the manifest with HMAC-SHA256 keyed by the server secret
+    pub fn sign(
+        secret: &[u8],
+        format: &str,
+        body: &[u8],
+        row_count: usize,
+        generated_at: &str,
+    ) -> Self {
+        let content_sha256 = hex::encode(Sha256::digest(body));
+        let signing_input = Self::signing_input(format, row_count, &content_sha256, generated_at);
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+        mac.update(signing_input.as_bytes());
+        let signature = hex::encode(mac.finalize().into_bytes());
+
+        ExportManifest {
+            format: format.to_string(),
+            row_count,
+            content_sha256,
+            generated_at: generated_at.to_string(),
+            signature,
+        }
+    }
+
+    // Check an export body against a received manifest using a constant-time signature comparison
+    pub fn verify(&self, secret: &[u8], body: &[u8]) -> bool {
+        if hex::encode(Sha256::digest(body)) != self.content_sha256 {
+            return false;
+        }
+
+        let Ok(signature) = hex::decode(&self.signature) else {
+            return false;
+        };
+
+        let signing_input = Self::signing_input(
+            &self.format,
+            self.row_count,
+            &self.content_sha256,
+            &self.generated_at,
+        );
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+        mac.update(signing_input.as_bytes());
+        mac.verify_slice(&signature).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn sample_link(shortened_url: &str, original_url: &str, base_url: Option<&str>) -> UrlEntry {
+        let created_at = Utc.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap();
+        UrlEntry {
+            id: 1,
+            user_id: Some(7),
+            original_url: original_url.to_string(),
+            shortened_url: shortened_url.to_string(),
+            base_url: base_url.map(|s| s.to_string()),
+            media_type: None,
+            expires_at: None,
+            expiry_grace_seconds: None,
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    #[test]
+    fn test_netscape_bookmarks_use_short_url() {
+        let links = vec![sample_link(
+            "abc12345",
+            "https://example.com/page",
+            Some("https://go.example.com"),
+        )];
+
+        let html = render_netscape_bookmarks(&links, &HashMap::new(), BookmarkTarget::ShortUrl);
+
+        assert!(html.starts_with("<!DOCTYPE NETSCAPE-Bookmark-file-1>"));
+        assert!(html.contains(
+            "<DT><A HREF=\"https://go.example.com/shortened-url/abc12345\" ADD_DATE=\"1735787045\">https://example.com/page</A>"
+        ));
+    }
+
+    #[test]
+    fn test_netscape_bookmarks_use_original_url() {
+        let links = vec![sample_link(
+            "abc12345",
+            "https://example.com/page",
+            Some("https://go.example.com"),
+        )];
+
+        let html = render_netscape_bookmarks(&links, &HashMap::new(), BookmarkTarget::OriginalUrl);
+
+        assert!(html.contains(
+            "<DT><A HREF=\"https://example.com/page\" ADD_DATE=\"1735787045\">https://go.example.com/shortened-url/abc12345</A>"
+        ));
+    }
+
+    #[test]
+    fn test_netscape_bookmarks_escape_html() {
+        let links = vec![sample_link(
+            "abc12345",
+            "https://example.com/?a=1&b=\"<x>\"",
+            None,
+        )];
+
+        let html = render_netscape_bookmarks(&links, &HashMap::new(), BookmarkTarget::ShortUrl);
+
+        assert!(html.contains("HREF=\"https://example.com/?a=1&amp;b=&quot;&lt;x&gt;&quot;\""));
+        assert!(!html.contains("<x>"));
+    }
+
+    #[test]
+    fn test_netscape_bookmarks_folder_per_tag() {
+        let mut tagged = sample_link("tag12345", "https://example.com/tagged", None);
+        tagged.id = 1;
+        let mut untagged = sample_link("unt12345", "https://example.com/untagged", None);
+        untagged.id = 2;
+        let tags = HashMap::from([(1, vec!["launch".to_string(), "Campaign & co".to_string()])]);
+
+        let html = render_netscape_bookmarks(&[tagged, untagged], &tags, BookmarkTarget::ShortUrl);
+
+        let tagged_line =
+            "<DT><A HREF=\"https://example.com/tagged\" ADD_DATE=\"1735787045\">https://example.com/tagged</A>";
+        let campaign = html.find("<DT><H3>Campaign &amp; co</H3>").unwrap();
+        let launch = html.find("<DT><H3>launch</H3>").unwrap();
+        assert!(campaign < launch);
+        // A link with two tags is in both folders
+        assert_eq!(html.matches(tagged_line).count(), 2);
+        assert!(html.contains(&format!("            {}", tagged_line)));
+        // Untagged links sit directly in the Thalora folder
+        assert!(html.contains(
+            "\n        <DT><A HREF=\"https://example.com/untagged\" ADD_DATE=\"1735787045\">https://example.com/untagged</A>"
+        ));
+        assert_eq!(html.matches("<DT><H3>").count(), 3);
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let links = vec![
+            sample_link(
+                "abc12345",
+                "https://example.com/page",
+                Some("https://go.example.com"),
+            ),
+            sample_link("def67890", "https://example.com/?a=1,b=\"2\"", None),
+        ];
+
+        let csv = render_csv(&links);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines[0], "short_url,original_url,media_type,created_at");
+        assert_eq!(
+            lines[1],
+            "https://go.example.com/shortened-url/abc12345,https://example.com/page,,2025-01-02T03:04:05+00:00"
+        );
+        assert_eq!(
+            lines[2],
+            ",\"https://example.com/?a=1,b=\"\"2\"\"\",,2025-01-02T03:04:05+00:00"
+        );
+    }
+
+    #[test]
+    fn test_ndjson_export() {
+        let links = vec![sample_link(
+            "abc12345",
+            "https://example.com/page",
+            Some("https://go.example.com"),
+        )];
+
+        let ndjson = render_ndjson(&links);
+        let row: serde_json::Value = serde_json::from_str(ndjson.lines().next().unwrap()).unwrap();
+
+        assert_eq!(ndjson.lines().count(), 1);
+        assert_eq!(
+            row["short_url"],
+            "https://go.example.com/shortened-url/abc12345"
+        );
+        assert_eq!(row["original_url"], "https://example.com/page");
+    }
+
+    #[test]
+    fn test_export_manifest_round_trip() {
+        let body = b"short_url,original_url,media_type,created_at\n";
+        let manifest = ExportManifest::sign(b"secret", "csv", body, 0, "2025-01-02T03:04:05+00:00");
+
+        assert_eq!(manifest.content_sha256.len(), 64);
+        assert_eq!(manifest.signature.len(), 64);
+        assert!(manifest.verify(b"secret", body));
+    }
+
+    #[test]
+    fn test_export_manifest_detects_tampering() {
+        let body = b"https://go.example.com/shortened-url/abc12345\n";
+        let manifest = ExportManifest::sign(b"secret", "csv", body, 1, "2025-01-02T03:04:05+00:00");
+
+        // Altered body
+        assert!(!manifest.verify(b"secret", b"https://evil.example.com\n"));
+        // Wrong key
+        assert!(!manifest.verify(b"other-secret", body));
+        // Altered row count
+        let mut altered = manifest.clone();
+        altered.row_count = 2;
+        assert!(!altered.verify(b"secret", body));
+    }
+
+    #[test]
+    fn test_netscape_bookmarks_empty() {
+        let html = render_netscape_bookmarks(&[], &HashMap::new(), BookmarkTarget::ShortUrl);
+
+        assert!(html.contains("<H3>Thalora</H3>"));
+        assert!(!html.contains("<A HREF"));
+    }
+}
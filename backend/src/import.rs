@@ -0,0 +1,161 @@
+// Largest CSV upload accepted by POST /api/urls/import; comfortably fits MAX_BATCH_IMPORT rows of
+// long URLs with tags
+pub const MAX_IMPORT_BYTES: usize = 4 * 1024 * 1024;
+
+// One link to import, as read from a CSV row
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    // 1-based line number in the uploaded file, for error reporting
+    pub line: usize,
+    pub original_url: String,
+    // Raw semicolon-separated tag list; empty when the file has no tags column
+    pub tags: String,
+}
+
+// Split CSV text into records of fields, honouring double-quoted fields with "" escapes and line
+// breaks. Returns each record with the line it starts on.
+fn parse_csv_records(body: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = body.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push((record_line, std::mem::take(&mut record)));
+                line += 1;
+                record_line = line;
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(format!(
+            "Unterminated quoted field starting on line {}",
+            record_line
+        ));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((record_line, record));
+    }
+
+    // Blank lines carry no link
+    records.retain(|(_, fields)| !(fields.len() == 1 && fields[0].trim().is_empty()));
+    Ok(records)
+}
+
+// Read the links from an import CSV. The header row must name an `original_url` (or `url`) column;
+// an optional `tags` column holds semicolon-separated tags and other columns are ignored, so a CSV
+// export can be imported as-is.
+pub fn parse_import_csv(body: &str) -> Result<Vec<ImportRow>, String> {
+    let body = body.strip_prefix('\u{feff}').unwrap_or(body);
+    let mut records = parse_csv_records(body)?.into_iter();
+    let (_, header) = records
+        .next()
+        .ok_or_else(|| "The CSV file is empty".to_string())?;
+
+    let column = |names: &[&str]| {
+        header
+            .iter()
+            .position(|name| names.contains(&name.trim().to_lowercase().as_str()))
+    };
+    let url_column = column(&["original_url", "url"])
+        .ok_or_else(|| "The CSV header must include an original_url or url column".to_string())?;
+    let tags_column = column(&["tags"]);
+
+    Ok(records
+        .map(|(line, fields)| ImportRow {
+            line,
+            original_url: fields.get(url_column).cloned().unwrap_or_default(),
+            tags: tags_column
+                .and_then(|column| fields.get(column).cloned())
+                .unwrap_or_default(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_import_csv_with_tags() {
+        let rows = parse_import_csv(
+            "url,tags\r\nhttps://example.com/a,campaign;q3\r\nhttps://example.com/b,\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ImportRow {
+                    line: 2,
+                    original_url: "https://example.com/a".to_string(),
+                    tags: "campaign;q3".to_string(),
+                },
+                ImportRow {
+                    line: 3,
+                    original_url: "https://example.com/b".to_string(),
+                    tags: String::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_import_csv_accepts_export_format() {
+        let rows = parse_import_csv(
+            "short_url,original_url,media_type,created_at\n\
+             https://go.example.com/shortened-url/abc12345,\"https://example.com/?a=1,2\",webpage,2026-10-17T00:00:00+00:00\n",
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].original_url, "https://example.com/?a=1,2");
+        assert_eq!(rows[0].tags, "");
+    }
+
+    #[test]
+    fn test_parse_import_csv_quoted_fields_and_line_numbers() {
+        let rows = parse_import_csv(
+            "original_url,tags\n\"https://example.com/\"\"q\"\"\",\"multi\nline\"\n\nhttps://example.com/c,x\n",
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].original_url, "https://example.com/\"q\"");
+        assert_eq!(rows[0].tags, "multi\nline");
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(rows[1].line, 5);
+    }
+
+    #[test]
+    fn test_parse_import_csv_errors() {
+        assert!(parse_import_csv("").is_err());
+        assert!(parse_import_csv("short_url,tags\nx,y\n").is_err());
+        assert!(parse_import_csv("url\n\"https://example.com\n").is_err());
+    }
+}
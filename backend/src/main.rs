@@ -1,5 +1,7 @@
 use actix_cors::Cors;
-use actix_session::{config::PersistentSession, storage::CookieSessionStore, SessionMiddleware};
+use actix_session::{
+    config::PersistentSession, storage::CookieSessionStore, Session, SessionMiddleware,
+};
 use actix_web::{
     cookie::Key, middleware::Logger, web, App, HttpRequest, HttpResponse, HttpServer, Result,
 };
@@ -10,22 +12,278 @@ use serde::{Deserialize, Serialize};
 use url::Url;
 
 mod auth;
+mod config;
 mod database;
-
-use auth::auth::{login_begin, login_complete, logout, me, register_begin, register_complete, test_mode_info};
+mod domain_strategy;
+mod export;
+mod expiry;
+mod import;
+mod media_type;
+mod rate_limit;
+mod safety;
+mod stats;
+mod tags;
+mod webhook;
+
+use auth::auth::{
+    login_begin, login_complete, logout, me, register_begin, register_complete, require_admin,
+    test_mode_info, AuthConfig,
+};
+use config::{BatchLimits, Settings};
 use database::{create_connection_pool, DatabaseConfig, DatabasePool, DatabaseService};
+use domain_strategy::{
+    normalize_domain, resolve_base_url, BaseUrlContext, DefaultDomainStrategy, ResolvedBaseUrl,
+};
+use export::{
+    render_csv, render_ndjson, render_netscape_bookmarks, BookmarkTarget, ExportManifest,
+    ExportSigning, MAX_VERIFY_EXPORT_BYTES,
+};
+use expiry::{render_grace_page, ExpiryConfig, ExpiryState};
+use import::{parse_import_csv, MAX_IMPORT_BYTES};
+use media_type::{MediaTypeService, MEDIA_TYPE_CATEGORIES};
+use rate_limit::{BucketStatus, RateLimitBucket, RateLimitTier, RateLimiter};
+use safety::{render_interstitial, FlaggedTargetResponse, SafetyCheckService};
+use tags::{parse_tag_list, validate_tags};
+use webhook::{WebhookEvent, WebhookService};
 
 // Data structures for request/response
 #[derive(Deserialize)]
 struct ShortenRequest {
     url: String,
     domain: Option<String>,
+    // Link stops redirecting this many seconds after creation
+    expires_in_seconds: Option<i64>,
+    // Overrides EXPIRED_LINK_GRACE_SECONDS for this link
+    expiry_grace_seconds: Option<i64>,
+}
+
+// Settings applied to every link in a batch unless the item overrides them
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct BatchShortenDefaults {
+    domain: Option<String>,
+    expires_in_seconds: Option<i64>,
+    expiry_grace_seconds: Option<i64>,
+    tags: Option<Vec<String>>,
+}
+
+// Either a bare URL or an object overriding some of the batch defaults
+enum BatchShortenItem {
+    Url(String),
+    Custom(BatchShortenOverride),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchShortenOverride {
+    url: String,
+    domain: Option<String>,
+    expires_in_seconds: Option<i64>,
+    expiry_grace_seconds: Option<i64>,
+    // Replaces the default tags rather than adding to them
+    tags: Option<Vec<String>>,
+}
+
+impl BatchShortenItem {
+    // Read one entry of `urls`. Items are parsed one at a time so the error names the item and
+    // any unsupported field, which an untagged enum would reduce to "did not match any variant".
+    fn parse(value: serde_json::Value) -> std::result::Result<Self, String> {
+        match value {
+            serde_json::Value::String(url) => Ok(BatchShortenItem::Url(url)),
+            value @ serde_json::Value::Object(_) => serde_json::from_value(value)
+                .map(BatchShortenItem::Custom)
+                .map_err(|e| e.to_string()),
+            _ => Err("expected a URL string or an object with a url".to_string()),
+        }
+    }
+
+    // The single-link request this item amounts to once the defaults are applied, with its tags
+    fn with_defaults(self, defaults: &BatchShortenDefaults) -> (ShortenRequest, Vec<String>) {
+        match self {
+            BatchShortenItem::Url(url) => (
+                ShortenRequest {
+                    url,
+                    domain: defaults.domain.clone(),
+                    expires_in_seconds: defaults.expires_in_seconds,
+                    expiry_grace_seconds: defaults.expiry_grace_seconds,
+                },
+                defaults.tags.clone().unwrap_or_default(),
+            ),
+            BatchShortenItem::Custom(item) => (
+                ShortenRequest {
+                    url: item.url,
+                    domain: item.domain.or_else(|| defaults.domain.clone()),
+                    expires_in_seconds: item.expires_in_seconds.or(defaults.expires_in_seconds),
+                    expiry_grace_seconds: item
+                        .expiry_grace_seconds
+                        .or(defaults.expiry_grace_seconds),
+                },
+                item.tags
+                    .or_else(|| defaults.tags.clone())
+                    .unwrap_or_default(),
+            ),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BatchShortenRequest {
+    #[serde(default)]
+    defaults: BatchShortenDefaults,
+    // Read with BatchShortenItem::parse
+    urls: Vec<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct BatchShortenResponse {
+    links: Vec<ShortenResponse>,
 }
 
 #[derive(Serialize, Deserialize)]
 struct ShortenResponse {
     short_url: String,
     original_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Deserialize)]
+struct RenewUrlRequest {
+    expires_in_seconds: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RenewUrlResponse {
+    short_id: String,
+    expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+// Every configurable attribute of a link, with the defaults that apply to it filled in
+#[derive(Serialize, Deserialize)]
+struct LinkConfigResponse {
+    short_id: String,
+    short_url: Option<String>,
+    original_url: String,
+    base_url: Option<String>,
+    owner_user_id: Option<i64>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    // Grace window in effect, and whether it comes from the link or EXPIRED_LINK_GRACE_SECONDS
+    expiry_grace_seconds: i64,
+    expiry_grace_source: String,
+    expiry_state: String,
+    media_type: Option<String>,
+    tags: Vec<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+    // Only included when an admin views someone else's link
+    #[serde(skip_serializing_if = "Option::is_none")]
+    creator: Option<database::UrlCreator>,
+}
+
+// A link the caller may manage, and whether access comes from admin rights rather than ownership
+struct ManagedLink {
+    entry: database::UrlEntry,
+    via_admin: bool,
+}
+
+// PATCH body for a link; omitted fields are untouched and null clears a nullable field
+#[derive(Deserialize)]
+struct UpdateUrlRequest {
+    url: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_present")]
+    expires_at: Option<Option<chrono::DateTime<chrono::Utc>>>,
+    #[serde(default, deserialize_with = "deserialize_present")]
+    expiry_grace_seconds: Option<Option<i64>>,
+}
+
+// Distinguish a field sent as null (Some(None)) from one left out (None, via serde default)
+fn deserialize_present<'de, D, T>(deserializer: D) -> std::result::Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+struct StatusBatchRequest {
+    short_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ShortIdStatus {
+    short_id: String,
+    // active, expired or not_found
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct DailyStatsQuery {
+    // "json" (default) or "csv"; when omitted, an Accept header asking for text/csv selects CSV
+    format: Option<String>,
+    // Number of days up to and including today (UTC)
+    days: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct DailyStatsResponse {
+    short_id: String,
+    days: Vec<stats::DailyCount>,
+    // Only included when an admin views someone else's link
+    #[serde(skip_serializing_if = "Option::is_none")]
+    creator: Option<database::UrlCreator>,
+}
+
+// Longest range the daily stats endpoint returns
+const MAX_DAILY_STATS_DAYS: i64 = 366;
+
+#[derive(Serialize, Deserialize)]
+struct StatusBatchResponse {
+    statuses: Vec<ShortIdStatus>,
+}
+
+#[derive(Deserialize)]
+struct AdminUpdateUserRequest {
+    rate_limit_tier: String,
+}
+
+// Result of a test delivery; never includes the signing secret
+#[derive(Serialize)]
+struct WebhookTestResponse {
+    event_id: String,
+    event_type: String,
+    delivered: bool,
+    status: Option<u16>,
+    response_time_ms: u64,
+    error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AdminUserResponse {
+    id: i64,
+    username: String,
+    rate_limit_tier: String,
+}
+
+#[derive(Serialize)]
+struct RateLimitStatusResponse {
+    buckets: Vec<BucketStatus>,
+}
+
+// Longest expiry or grace window accepted for a link (10 years)
+const MAX_LINK_LIFETIME_SECONDS: i64 = 10 * 365 * 24 * 60 * 60;
+
+#[derive(Deserialize)]
+struct ResolveDomainQuery {
+    domain: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ResolveDomainResponse {
+    // None when the development fallback was used
+    domain: Option<String>,
+    base_url: String,
+    reason: String,
 }
 
 #[derive(Deserialize)]
@@ -41,11 +299,119 @@ struct AddDomainResponse {
     verification_status: String,
 }
 
+#[derive(Serialize, Deserialize)]
+struct SetDefaultDomainResponse {
+    id: i64,
+    domain_name: String,
+    is_default: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ValidateDomainResponse {
+    domain_name: String,
+    normalized_domain_name: String,
+    is_valid: bool,
+    is_taken: bool,
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RegenerateTokensRequest {
+    // Limit regeneration to these domains; all domains when omitted
+    domain_ids: Option<Vec<i64>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RegeneratedTokenResponse {
+    id: i64,
+    domain_name: String,
+    txt_record_name: String,
+    verification_token: String,
+    verification_status: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RegenerateTokensResponse {
+    regenerated: Vec<RegeneratedTokenResponse>,
+    not_found: Vec<i64>,
+}
+
 #[derive(Serialize, Deserialize)]
 struct ErrorResponse {
     error: String,
 }
 
+// Summary of a CSV import: the links created and every problem found, by line
+#[derive(Serialize)]
+struct ImportResponse {
+    imported: usize,
+    // Rows not imported because their URL was invalid
+    skipped: usize,
+    links: Vec<ImportedLink>,
+    errors: Vec<ImportRowError>,
+}
+
+#[derive(Serialize)]
+struct ImportedLink {
+    line: usize,
+    short_url: String,
+    original_url: String,
+    tags: Vec<String>,
+}
+
+// A row that was skipped, or a tag that was left off an imported link
+#[derive(Serialize)]
+struct ImportRowError {
+    line: usize,
+    error: String,
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    format: Option<String>,
+    // "short" (default) or "target" - which address each exported bookmark points at
+    link: Option<String>,
+    // Return an HMAC-signed integrity manifest in the response headers
+    sign: Option<bool>,
+    // Only export links whose target is in this media category (see MEDIA_TYPE_CATEGORIES)
+    media_type: Option<String>,
+}
+
+// Check an export's media_type filter, returning the category to keep or the error to report
+fn parse_media_type_filter(
+    value: Option<&str>,
+) -> std::result::Result<Option<&'static str>, String> {
+    let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else {
+        return Ok(None);
+    };
+
+    MEDIA_TYPE_CATEGORIES
+        .iter()
+        .find(|category| category.eq_ignore_ascii_case(value))
+        .map(|category| Some(*category))
+        .ok_or_else(|| {
+            format!(
+                "Unsupported media_type '{}'. Use one of: {}",
+                value,
+                MEDIA_TYPE_CATEGORIES.join(", ")
+            )
+        })
+}
+
+#[derive(Serialize, Deserialize)]
+struct MediaTypeStatsResponse {
+    counts: std::collections::BTreeMap<String, i64>,
+}
+
+// Headers carrying a signed export's integrity manifest
+const EXPORT_MANIFEST_HEADERS: [&str; 5] = [
+    "x-export-format",
+    "x-export-row-count",
+    "x-export-content-sha256",
+    "x-export-generated-at",
+    "x-export-signature",
+];
+
 // Database service for URL mappings - now uses connection pool
 type AppDatabasePool = web::Data<DatabasePool>;
 
@@ -68,25 +434,29 @@ impl DomainValidationService {
         )
     }
 
-    // Basic domain validation - checks format and creates verification token
-    async fn validate_domain(domain: &str) -> (bool, String, Option<String>) {
-        // Basic format validation
+    // Check a normalized domain name's format without generating a verification token
+    fn check_domain_format(domain: &str) -> std::result::Result<(), String> {
         if domain.is_empty() {
-            return (false, "Domain cannot be empty".to_string(), None);
+            return Err("Domain cannot be empty".to_string());
         }
 
         if domain.len() > 253 {
-            return (
-                false,
-                "Domain name too long (max 253 characters)".to_string(),
-                None,
-            );
+            return Err("Domain name too long (max 253 characters)".to_string());
         }
 
         // Check for valid domain format (basic)
         let domain_regex = regex::Regex::new(r"^[a-zA-Z0-9]([a-zA-Z0-9\-]{0,61}[a-zA-Z0-9])?(\.[a-zA-Z0-9]([a-zA-Z0-9\-]{0,61}[a-zA-Z0-9])?)*$").unwrap();
         if !domain_regex.is_match(domain) {
-            return (false, "Invalid domain format".to_string(), None);
+            return Err("Invalid domain format".to_string());
+        }
+
+        Ok(())
+    }
+
+    // Basic domain validation - checks format and creates verification token
+    async fn validate_domain(domain: &str) -> (bool, String, Option<String>) {
+        if let Err(message) = Self::check_domain_format(domain) {
+            return (false, message, None);
         }
 
         // Generate verification token
@@ -100,18 +470,20 @@ impl DomainValidationService {
     }
 
     // Check DNS TXT record for domain verification
-    async fn verify_dns_txt_record(domain: &str, expected_token: &str) -> bool {
+    async fn verify_dns_txt_record(
+        domain: &str,
+        expected_token: &str,
+        domain_verification: &DomainVerification,
+    ) -> bool {
         info!(
             "Checking DNS TXT record for domain: {} with token: {}",
             domain, expected_token
         );
 
         // Check if verification should be skipped (development mode)
-        if let Ok(skip_verification) = std::env::var("SKIP_DOMAIN_VERIFICATION") {
-            if skip_verification.to_lowercase() == "true" {
-                info!("DNS verification skipped (SKIP_DOMAIN_VERIFICATION=true)");
-                return true;
-            }
+        if domain_verification.skip {
+            info!("DNS verification skipped (SKIP_DOMAIN_VERIFICATION=true)");
+            return true;
         }
 
         // Perform actual DNS TXT record lookup
@@ -168,15 +540,144 @@ impl DomainValidationService {
     }
 }
 
+// Scheme used when building short links on a custom domain (targets are always validated as HTTPS)
+struct ShortLinkScheme(&'static str);
+
+impl ShortLinkScheme {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "https" => Some(ShortLinkScheme("https")),
+            "http" => Some(ShortLinkScheme("http")),
+            _ => None,
+        }
+    }
+
+    // Read SHORT_LINK_SCHEME (default https)
+    fn from_env(settings: &Settings) -> anyhow::Result<Self> {
+        match settings.get("SHORT_LINK_SCHEME") {
+            Some(value) => Self::parse(&value).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid SHORT_LINK_SCHEME '{}'. Expected 'https' or 'http'",
+                    value
+                )
+            }),
+            None => Ok(ShortLinkScheme("https")),
+        }
+    }
+}
+
+// Whether custom domains must prove ownership with a DNS TXT record
+struct DomainVerification {
+    // Accept domains without the DNS check and allow the localhost fallback (development only)
+    skip: bool,
+}
+
+impl DomainVerification {
+    // Read SKIP_DOMAIN_VERIFICATION (default false)
+    fn from_env(settings: &Settings) -> Self {
+        DomainVerification {
+            skip: settings.flag("SKIP_DOMAIN_VERIFICATION"),
+        }
+    }
+}
+
+// Length of generated short IDs
+const SHORT_ID_LENGTH: usize = 8;
+
+// How a redirect path is matched to a short ID
+struct SlugMatching {
+    // Redirect on a valid short ID followed by trailing junk (e.g. pasted punctuation)
+    tolerate_suffix: bool,
+}
+
+impl SlugMatching {
+    // Read TOLERATE_SLUG_SUFFIX (default false)
+    fn from_env(settings: &Settings) -> Self {
+        SlugMatching {
+            tolerate_suffix: settings.flag("TOLERATE_SLUG_SUFFIX"),
+        }
+    }
+
+    // The short ID at the start of a path with trailing data, when suffixes are tolerated.
+    // Short IDs are fixed-length alphanumeric, so the boundary is unambiguous.
+    fn prefix_slug<'a>(&self, path: &'a str) -> Option<&'a str> {
+        if !self.tolerate_suffix || path.len() <= SHORT_ID_LENGTH {
+            return None;
+        }
+
+        let prefix = path.get(..SHORT_ID_LENGTH)?;
+        prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric())
+            .then_some(prefix)
+    }
+}
+
 // Generate a random shortened URL identifier
 fn generate_short_id() -> String {
     thread_rng()
         .sample_iter(&Alphanumeric)
-        .take(8)
+        .take(SHORT_ID_LENGTH)
         .map(char::from)
         .collect()
 }
 
+// Check the settings of a link about to be created, or return the validation error to report
+fn validate_new_link(req: &ShortenRequest) -> std::result::Result<(), String> {
+    let original_url = req.url.trim();
+    if original_url.is_empty() {
+        return Err("URL cannot be empty".to_string());
+    }
+
+    if !is_valid_url(original_url) {
+        return Err(
+            "Invalid URL format. Only HTTPS URLs are supported for security reasons.".to_string(),
+        );
+    }
+
+    if let Some(seconds) = req.expires_in_seconds {
+        if !(1..=MAX_LINK_LIFETIME_SECONDS).contains(&seconds) {
+            return Err(format!(
+                "expires_in_seconds must be between 1 and {}",
+                MAX_LINK_LIFETIME_SECONDS
+            ));
+        }
+    }
+
+    if let Some(seconds) = req.expiry_grace_seconds {
+        if req.expires_in_seconds.is_none() {
+            return Err("expiry_grace_seconds requires expires_in_seconds".to_string());
+        }
+        if !(0..=MAX_LINK_LIFETIME_SECONDS).contains(&seconds) {
+            return Err(format!(
+                "expiry_grace_seconds must be between 0 and {}",
+                MAX_LINK_LIFETIME_SECONDS
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// Generate a short ID that is not stored yet and not among the IDs already reserved by this request
+async fn generate_unique_short_id(
+    db_pool: &DatabasePool,
+    reserved: &std::collections::HashSet<String>,
+) -> anyhow::Result<String> {
+    loop {
+        let candidate = generate_short_id();
+        if reserved.contains(&candidate) || DatabaseService::url_exists(db_pool, &candidate).await? {
+            // If it exists, continue the loop to generate a new one
+            warn!(
+                "Generated short ID {} already exists, trying again",
+                candidate
+            );
+            continue;
+        }
+        return Ok(candidate);
+    }
+}
+
 // Validate URL format - HTTPS only for security
 fn is_valid_url(url_str: &str) -> bool {
     match Url::parse(url_str) {
@@ -188,60 +689,179 @@ fn is_valid_url(url_str: &str) -> bool {
     }
 }
 
+// Run base URL resolution for a request, logging the outcome.
+// Returns the error response to send when no base URL can be used.
+async fn resolve_request_base_url(
+    http_req: &HttpRequest,
+    db_pool: &DatabasePool,
+    user_id: Option<i64>,
+    requested_domain: Option<&str>,
+    default_domain_strategy: &DefaultDomainStrategy,
+    short_link_scheme: &ShortLinkScheme,
+    domain_verification: &DomainVerification,
+) -> std::result::Result<ResolvedBaseUrl, HttpResponse> {
+    let domains = match DatabaseService::get_verified_domains(db_pool, user_id).await {
+        Ok(domains) => domains,
+        Err(e) => {
+            error!("Failed to retrieve domains: {}", e);
+            return Err(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve domain information".to_string(),
+            }));
+        }
+    };
+
+    let connection_info = http_req.connection_info();
+    let context = BaseUrlContext {
+        user_id,
+        short_link_scheme: short_link_scheme.0,
+        strategy: *default_domain_strategy,
+        // Allow fallback to localhost in development
+        allow_dev_fallback: domain_verification.skip,
+        connection_scheme: connection_info.scheme(),
+        connection_host: connection_info.host(),
+    };
+
+    match resolve_base_url(&domains, requested_domain, &context) {
+        Ok(resolved) => {
+            info!(
+                "Using base URL {} (reason: {}, strategy: {})",
+                resolved.base_url,
+                resolved.reason.as_str(),
+                default_domain_strategy.as_str()
+            );
+            Ok(resolved)
+        }
+        Err(e) => {
+            info!("Could not resolve a base URL: {}", e);
+            Err(HttpResponse::BadRequest().json(ErrorResponse {
+                error: e.to_string(),
+            }))
+        }
+    }
+}
+
+// GET /shorten/resolve-domain endpoint - show which domain a new link would use, without creating one
+async fn resolve_domain(
+    query: web::Query<ResolveDomainQuery>,
+    http_req: HttpRequest,
+    session: Session,
+    db_pool: AppDatabasePool,
+    default_domain_strategy: web::Data<DefaultDomainStrategy>,
+    short_link_scheme: web::Data<ShortLinkScheme>,
+    domain_verification: web::Data<DomainVerification>,
+) -> Result<HttpResponse> {
+    let Some(user_id) = session.get::<i64>("user_id")? else {
+        return Ok(HttpResponse::Unauthorized().json(ErrorResponse {
+            error: "Not authenticated".to_string(),
+        }));
+    };
+
+    match resolve_request_base_url(
+        &http_req,
+        &db_pool,
+        Some(user_id),
+        query.domain.as_deref(),
+        &default_domain_strategy,
+        &short_link_scheme,
+        &domain_verification,
+    )
+    .await
+    {
+        Ok(resolved) => Ok(HttpResponse::Ok().json(ResolveDomainResponse {
+            domain: resolved.domain_name,
+            base_url: resolved.base_url,
+            reason: resolved.reason.as_str().to_string(),
+        })),
+        Err(response) => Ok(response),
+    }
+}
+
 // POST /shorten endpoint
+#[allow(clippy::too_many_arguments)] // one parameter per actix extractor
 async fn shorten_url(
     req: web::Json<ShortenRequest>,
     http_req: HttpRequest,
+    session: Session,
     db_pool: AppDatabasePool,
+    default_domain_strategy: web::Data<DefaultDomainStrategy>,
+    short_link_scheme: web::Data<ShortLinkScheme>,
+    domain_verification: web::Data<DomainVerification>,
+    rate_limiter: web::Data<RateLimiter>,
 ) -> Result<HttpResponse> {
+    let rate_limit_tier = rate_limit::caller_tier(&session, &db_pool).await;
+    if let Some(response) = rate_limit::enforce(
+        &rate_limiter,
+        RateLimitBucket::Shorten,
+        rate_limit_tier,
+        &session,
+        &http_req,
+    ) {
+        return Ok(response);
+    }
+
     let original_url = req.url.trim();
 
+    // Links created while signed in are owned by that user
+    let user_id: Option<i64> = session.get("user_id").unwrap_or(None);
+
     // Log the incoming request
     info!("Received shorten request for URL: {original_url}");
 
-    // Validate URL
-    if original_url.is_empty() {
-        info!("Empty URL provided");
-        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
-            error: "URL cannot be empty".to_string(),
-        }));
+    // Validate URL and expiry settings
+    if let Err(error) = validate_new_link(&req) {
+        info!("Rejected shorten request for {original_url}: {error}");
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse { error }));
     }
 
-    if !is_valid_url(original_url) {
-        info!("Invalid URL provided: {original_url}");
-        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
-            error: "Invalid URL format. Only HTTPS URLs are supported for security reasons."
-                .to_string(),
-        }));
-    }
+    let expires_at = req
+        .expires_in_seconds
+        .map(|seconds| chrono::Utc::now() + chrono::Duration::seconds(seconds));
 
-    // Generate unique short ID, ensuring it's not already used
-    let short_id = loop {
-        let candidate = generate_short_id();
+    // Check for verified custom domains - use specified domain or the configured default.
+    // Resolved before the link is stored so the link records the base URL it was issued on.
+    let base_url = match resolve_request_base_url(
+        &http_req,
+        &db_pool,
+        user_id,
+        req.domain.as_deref(),
+        &default_domain_strategy,
+        &short_link_scheme,
+        &domain_verification,
+    )
+    .await
+    {
+        Ok(resolved) => resolved.base_url,
+        Err(response) => return Ok(response),
+    };
 
-        // Check if this ID already exists in the database using the pool
-        match DatabaseService::url_exists(&db_pool, &candidate).await {
-            Ok(exists) => {
-                if !exists {
-                    break candidate;
-                }
-                // If it exists, continue the loop to generate a new one
-                warn!(
-                    "Generated short ID {} already exists, trying again",
-                    candidate
-                );
-            }
+    // Generate unique short ID, ensuring it's not already used
+    let short_id =
+        match generate_unique_short_id(&db_pool, &std::collections::HashSet::new()).await {
+            Ok(short_id) => short_id,
             Err(e) => {
                 error!("Database error checking URL existence: {}", e);
                 return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
                     error: "Database error".to_string(),
                 }));
             }
-        }
+        };
+
+    // Anonymous links record the client IP so admins can attribute them when investigating abuse
+    let creator_ip = match user_id {
+        Some(_) => None,
+        None => rate_limiter.client_ip(&http_req),
     };
 
     // Store the mapping in the database using the pool
-    match DatabaseService::insert_url(&db_pool, original_url, &short_id).await {
+    let new_url = database::NewUrl {
+        original_url: original_url.to_string(),
+        shortened_url: short_id.clone(),
+        base_url: base_url.clone(),
+        expires_at,
+        expiry_grace_seconds: req.expiry_grace_seconds,
+        tags: Vec::new(),
+    };
+    match DatabaseService::insert_url(&db_pool, &new_url, user_id, creator_ip.as_deref()).await {
         Ok(id) => {
             info!(
                 "Created short URL {} for {} with database ID {}",
@@ -256,108 +876,1294 @@ async fn shorten_url(
         }
     }
 
-    // Check for verified custom domains - use specified domain or first available one
-    let base_url = match DatabaseService::get_verified_domains(&db_pool).await {
-        Ok(domains) => {
-            // If a specific domain was requested, try to use it
-            if let Some(requested_domain) = &req.domain {
-                if let Some(domain) = domains.iter().find(|d| d.domain_name == *requested_domain) {
-                    info!("Using requested custom domain: {}", domain.domain_name);
-                    format!("https://{}", domain.domain_name)
-                } else {
-                    // Requested domain not found or not verified
-                    info!(
-                        "Requested domain '{}' not found or not verified",
-                        requested_domain
-                    );
-                    return Ok(HttpResponse::BadRequest().json(ErrorResponse {
-                        error: format!(
-                            "Domain '{}' is not verified or does not exist",
-                            requested_domain
-                        ),
-                    }));
-                }
-            } else if let Some(domain) = domains.first() {
-                info!(
-                    "Using first available custom domain: {}",
-                    domain.domain_name
-                );
-                format!("https://{}", domain.domain_name)
-            } else {
-                // Check if we allow fallback to localhost in development
-                let skip_verification = std::env::var("SKIP_DOMAIN_VERIFICATION")
-                    .unwrap_or_else(|_| "false".to_string())
-                    .to_lowercase()
-                    == "true";
-
-                if skip_verification {
-                    info!("No verified domains available, using localhost fallback (development mode)");
-                    // Fall back to default domain in development
-                    let connection_info = http_req.connection_info();
-                    let scheme = connection_info.scheme();
-                    let host = connection_info.host();
-
-                    // Fallback to localhost:8080 if connection info is not reliable
-                    if host.is_empty() || scheme.is_empty() {
-                        info!("Connection info not reliable (scheme: '{}', host: '{}'), falling back to localhost:8080", scheme, host);
-                        "http://localhost:8080".to_string()
-                    } else {
-                        format!("{}://{}", scheme, host)
-                    }
-                } else {
-                    error!("No verified domains available and fallback disabled (production mode)");
-                    return Ok(HttpResponse::BadRequest().json(ErrorResponse {
-                        error: "No verified domains available for URL shortening. Please add and verify a custom domain first.".to_string(),
+    // Return the shortened URL
+    Ok(HttpResponse::Ok().json(ShortenResponse {
+        short_url: format!("{}/shortened-url/{}", base_url, short_id),
+        original_url: original_url.to_string(),
+        expires_at,
+    }))
+}
+
+// POST /shorten/batch-with-defaults endpoint - create several links sharing domain and expiry
+// settings, with per-item overrides; all links are created or none are
+#[allow(clippy::too_many_arguments)] // one parameter per actix extractor
+async fn shorten_batch_with_defaults(
+    req: web::Json<BatchShortenRequest>,
+    http_req: HttpRequest,
+    session: Session,
+    db_pool: AppDatabasePool,
+    default_domain_strategy: web::Data<DefaultDomainStrategy>,
+    short_link_scheme: web::Data<ShortLinkScheme>,
+    domain_verification: web::Data<DomainVerification>,
+    rate_limiter: web::Data<RateLimiter>,
+    batch_limits: web::Data<BatchLimits>,
+) -> Result<HttpResponse> {
+    let Some(user_id) = session.get::<i64>("user_id")? else {
+        return Ok(HttpResponse::Unauthorized().json(ErrorResponse {
+            error: "Not authenticated".to_string(),
+        }));
+    };
+
+    let BatchShortenRequest { defaults, urls } = req.into_inner();
+    if urls.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "urls cannot be empty".to_string(),
+        }));
+    }
+    if urls.len() > batch_limits.shorten {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!(
+                "At most {} URLs can be shortened per request (MAX_BATCH_SHORTEN)",
+                batch_limits.shorten
+            ),
+        }));
+    }
+
+    // Each link counts against the shorten limit, as if it were created on its own
+    let rate_limit_tier = rate_limit::caller_tier(&session, &db_pool).await;
+    if let Some(response) = rate_limit::enforce_n(
+        &rate_limiter,
+        RateLimitBucket::Shorten,
+        rate_limit_tier,
+        urls.len() as u32,
+        &session,
+        &http_req,
+    ) {
+        return Ok(response);
+    }
+
+    let mut requests = Vec::with_capacity(urls.len());
+    let mut link_tags = Vec::with_capacity(urls.len());
+    for (index, item) in urls.into_iter().enumerate() {
+        let validated = BatchShortenItem::parse(item).and_then(|item| {
+            let (request, tags) = item.with_defaults(&defaults);
+            validate_new_link(&request)?;
+            let (tags, tag_errors) = validate_tags(tags.iter().map(String::as_str));
+            match tag_errors.into_iter().next() {
+                Some(error) => Err(error),
+                None => Ok((request, tags)),
+            }
+        });
+        match validated {
+            Ok((request, tags)) => {
+                requests.push(request);
+                link_tags.push(tags);
+            }
+            Err(error) => {
+                info!("Rejected batch shorten request from user {user_id}: urls[{index}]: {error}");
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                    error: format!("urls[{}]: {}", index, error),
+                }));
+            }
+        }
+    }
+
+    // Resolve each distinct domain once; the batch shares the same verified domains
+    let mut base_urls: std::collections::HashMap<Option<String>, String> =
+        std::collections::HashMap::new();
+    for request in &requests {
+        if base_urls.contains_key(&request.domain) {
+            continue;
+        }
+        match resolve_request_base_url(
+            &http_req,
+            &db_pool,
+            Some(user_id),
+            request.domain.as_deref(),
+            &default_domain_strategy,
+            &short_link_scheme,
+            &domain_verification,
+        )
+        .await
+        {
+            Ok(resolved) => {
+                base_urls.insert(request.domain.clone(), resolved.base_url);
+            }
+            Err(response) => return Ok(response),
+        }
+    }
+
+    let now = chrono::Utc::now();
+    let mut reserved = std::collections::HashSet::new();
+    let mut new_urls = Vec::with_capacity(requests.len());
+    for (request, tags) in requests.iter().zip(link_tags) {
+        let short_id = match generate_unique_short_id(&db_pool, &reserved).await {
+            Ok(short_id) => short_id,
+            Err(e) => {
+                error!("Database error checking URL existence: {}", e);
+                return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Database error".to_string(),
+                }));
+            }
+        };
+        reserved.insert(short_id.clone());
+
+        new_urls.push(database::NewUrl {
+            original_url: request.url.trim().to_string(),
+            shortened_url: short_id,
+            base_url: base_urls[&request.domain].clone(),
+            expires_at: request
+                .expires_in_seconds
+                .map(|seconds| now + chrono::Duration::seconds(seconds)),
+            expiry_grace_seconds: request.expiry_grace_seconds,
+            tags,
+        });
+    }
+
+    if let Err(e) = DatabaseService::insert_urls(&db_pool, user_id, &new_urls).await {
+        error!("Failed to store batch of URLs in database: {}", e);
+        return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to store URLs".to_string(),
+        }));
+    }
+
+    info!("Created {} short URLs for user {}", new_urls.len(), user_id);
+
+    Ok(HttpResponse::Ok().json(BatchShortenResponse {
+        links: new_urls
+            .into_iter()
+            .map(|url| ShortenResponse {
+                short_url: format!("{}/shortened-url/{}", url.base_url, url.shortened_url),
+                original_url: url.original_url,
+                expires_at: url.expires_at,
+            })
+            .collect(),
+    }))
+}
+
+// GET /shortened-url/{id} endpoint
+async fn redirect_url(
+    path: web::Path<String>,
+    session: Session,
+    db_pool: AppDatabasePool,
+    media_type_service: web::Data<MediaTypeService>,
+    expiry_config: web::Data<ExpiryConfig>,
+    slug_matching: web::Data<SlugMatching>,
+    safety_check_service: web::Data<SafetyCheckService>,
+) -> Result<HttpResponse> {
+    let short_id = path.into_inner();
+
+    info!("Received redirect request for short ID: {short_id}");
+
+    // Look up the URL entry in the database using the pool, trying the full path first and then
+    // the short ID without trailing data when TOLERATE_SLUG_SUFFIX is enabled
+    let candidates =
+        std::iter::once(short_id.as_str()).chain(slug_matching.prefix_slug(&short_id));
+    let mut url_entry = None;
+    for candidate in candidates {
+        match DatabaseService::get_url_by_short_id(&db_pool, candidate).await {
+            Ok(Some(entry)) => {
+                if candidate != short_id {
+                    info!("Matched {short_id} to short ID {candidate}, ignoring trailing data");
+                }
+                url_entry = Some(entry);
+                break;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                error!("Database error retrieving URL for {}: {}", candidate, e);
+                return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Database error".to_string(),
+                }));
+            }
+        }
+    }
+
+    match url_entry {
+        Some(entry) => {
+            match expiry_config.state_of(&entry, chrono::Utc::now()) {
+                ExpiryState::Active => {}
+                ExpiryState::InGrace => {
+                    info!("Short ID {short_id} expired and is in its grace window");
+                    let is_owner = entry.user_id.is_some()
+                        && session.get::<i64>("user_id").unwrap_or(None) == entry.user_id;
+                    return Ok(HttpResponse::Gone()
+                        .content_type("text/html; charset=utf-8")
+                        .append_header(("Cache-Control", "no-store"))
+                        .body(render_grace_page(&entry, is_owner)));
+                }
+                ExpiryState::Expired => {
+                    info!("Short ID {short_id} has expired");
+                    return Ok(HttpResponse::Gone().json(ErrorResponse {
+                        error: "Short URL has expired".to_string(),
+                    }));
+                }
+            }
+
+            // Re-check the target in case it was flagged after the link was created
+            if safety_check_service.is_enabled()
+                && safety_check_service.is_flagged(&entry.original_url).await
+            {
+                warn!(
+                    "Short ID {short_id} points to flagged target {}",
+                    entry.original_url
+                );
+                return Ok(match safety_check_service.flagged_response() {
+                    FlaggedTargetResponse::Interstitial => HttpResponse::Ok()
+                        .content_type("text/html; charset=utf-8")
+                        .body(render_interstitial(&entry.original_url)),
+                    FlaggedTargetResponse::Forbidden => HttpResponse::Forbidden().json(ErrorResponse {
+                        error: "The destination of this short URL has been flagged as unsafe"
+                            .to_string(),
+                    }),
+                });
+            }
+
+            // Count the redirect in the background so it never delays the visitor
+            {
+                let db_pool = db_pool.clone();
+                let url_id = entry.id;
+                actix_web::rt::spawn(async move {
+                    if let Err(e) = DatabaseService::record_click(&db_pool, url_id).await {
+                        warn!("Failed to record click for URL ID {}: {}", url_id, e);
+                    }
+                });
+            }
+
+            // Enrich the link with its target's media type in the background so the redirect is never delayed
+            if entry.media_type.is_none() && media_type_service.is_enabled() {
+                let db_pool = db_pool.clone();
+                let media_type_service = media_type_service.clone();
+                let url_id = entry.id;
+                let target_url = entry.original_url.clone();
+                actix_web::rt::spawn(async move {
+                    media_type_service
+                        .enrich(&db_pool, url_id, &target_url)
+                        .await;
+                });
+            }
+
+            info!("Redirecting {short_id} to {}", entry.original_url);
+            Ok(HttpResponse::Found()
+                .append_header(("Location", entry.original_url))
+                .finish())
+        }
+        None => {
+            info!("Short ID not found: {short_id}");
+            Ok(HttpResponse::NotFound().json(ErrorResponse {
+                error: "Short URL not found".to_string(),
+            }))
+        }
+    }
+}
+
+// Load a link the signed-in user may manage: their own, or any link for an administrator.
+// Returns the response to send when the link is missing or the caller is not allowed.
+async fn load_managed_link(
+    session: &Session,
+    db_pool: &DatabasePool,
+    auth_config: &AuthConfig,
+    short_id: &str,
+) -> std::result::Result<ManagedLink, HttpResponse> {
+    let user_id = match session.get::<i64>("user_id") {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => {
+            return Err(HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "Not authenticated".to_string(),
+            }));
+        }
+        Err(e) => {
+            error!("Session error: {}", e);
+            return Err(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Session error".to_string(),
+            }));
+        }
+    };
+
+    let entry = match DatabaseService::get_url_by_short_id(db_pool, short_id).await {
+        Ok(Some(entry)) => entry,
+        Ok(None) => {
+            return Err(HttpResponse::NotFound().json(ErrorResponse {
+                error: "Short URL not found".to_string(),
+            }));
+        }
+        Err(e) => {
+            error!("Database error retrieving URL for {}: {}", short_id, e);
+            return Err(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Database error".to_string(),
+            }));
+        }
+    };
+
+    let via_admin = entry.user_id != Some(user_id);
+    if via_admin {
+        require_admin(session, db_pool, auth_config).await?;
+    }
+
+    Ok(ManagedLink { entry, via_admin })
+}
+
+// Creator attribution for admin views of someone else's link; None for the link's owner
+async fn creator_for_admin(
+    db_pool: &DatabasePool,
+    link: &ManagedLink,
+) -> std::result::Result<Option<database::UrlCreator>, HttpResponse> {
+    if !link.via_admin {
+        return Ok(None);
+    }
+
+    match DatabaseService::get_url_creator(db_pool, link.entry.id).await {
+        Ok(creator) => Ok(creator),
+        Err(e) => {
+            error!(
+                "Database error retrieving creator of {}: {}",
+                link.entry.shortened_url, e
+            );
+            Err(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Database error".to_string(),
+            }))
+        }
+    }
+}
+
+// A link's tags for its config response, or the error response to send when they cannot be loaded
+async fn link_tags(
+    db_pool: &DatabasePool,
+    entry: &database::UrlEntry,
+) -> std::result::Result<Vec<String>, HttpResponse> {
+    DatabaseService::get_url_tags(db_pool, entry.id)
+        .await
+        .map_err(|e| {
+            error!(
+                "Database error retrieving tags of {}: {}",
+                entry.shortened_url, e
+            );
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Database error".to_string(),
+            })
+        })
+}
+
+// GET /urls/{id}/config endpoint - the full effective configuration of a link, for its owner or an admin
+async fn get_url_config(
+    path: web::Path<String>,
+    session: Session,
+    db_pool: AppDatabasePool,
+    auth_config: web::Data<AuthConfig>,
+    expiry_config: web::Data<ExpiryConfig>,
+) -> Result<HttpResponse> {
+    let short_id = path.into_inner();
+
+    let link = match load_managed_link(&session, &db_pool, &auth_config, &short_id).await {
+        Ok(link) => link,
+        Err(response) => return Ok(response),
+    };
+    let creator = match creator_for_admin(&db_pool, &link).await {
+        Ok(creator) => creator,
+        Err(response) => return Ok(response),
+    };
+    let tags = match link_tags(&db_pool, &link.entry).await {
+        Ok(tags) => tags,
+        Err(response) => return Ok(response),
+    };
+
+    Ok(HttpResponse::Ok().json(link_config_response(
+        link.entry,
+        &expiry_config,
+        tags,
+        creator,
+    )))
+}
+
+fn link_config_response(
+    entry: database::UrlEntry,
+    expiry_config: &ExpiryConfig,
+    tags: Vec<String>,
+    creator: Option<database::UrlCreator>,
+) -> LinkConfigResponse {
+    let expiry_grace_source = if entry.expiry_grace_seconds.is_some() {
+        "link"
+    } else {
+        "default"
+    };
+
+    LinkConfigResponse {
+        short_url: export::short_url_for(&entry),
+        expiry_grace_seconds: expiry_config.grace_period_for(&entry).num_seconds(),
+        expiry_grace_source: expiry_grace_source.to_string(),
+        expiry_state: expiry_config
+            .state_of(&entry, chrono::Utc::now())
+            .as_str()
+            .to_string(),
+        short_id: entry.shortened_url,
+        original_url: entry.original_url,
+        base_url: entry.base_url,
+        owner_user_id: entry.user_id,
+        expires_at: entry.expires_at,
+        media_type: entry.media_type,
+        tags,
+        created_at: entry.created_at,
+        updated_at: entry.updated_at,
+        creator,
+    }
+}
+
+// Check a PATCH body and turn it into the update to apply, or the validation error to report
+fn validate_url_update(
+    req: UpdateUrlRequest,
+    now: chrono::DateTime<chrono::Utc>,
+) -> std::result::Result<database::UrlUpdate, String> {
+    let original_url = match req.url {
+        Some(url) => {
+            let url = url.trim().to_string();
+            if url.is_empty() {
+                return Err("URL cannot be empty".to_string());
+            }
+            if !is_valid_url(&url) {
+                return Err(
+                    "Invalid URL format. Only HTTPS URLs are supported for security reasons."
+                        .to_string(),
+                );
+            }
+            Some(url)
+        }
+        None => None,
+    };
+
+    if let Some(Some(expires_at)) = req.expires_at {
+        if expires_at <= now
+            || expires_at > now + chrono::Duration::seconds(MAX_LINK_LIFETIME_SECONDS)
+        {
+            return Err(format!(
+                "expires_at must be in the future and at most {} seconds from now",
+                MAX_LINK_LIFETIME_SECONDS
+            ));
+        }
+    }
+
+    if let Some(Some(seconds)) = req.expiry_grace_seconds {
+        if !(0..=MAX_LINK_LIFETIME_SECONDS).contains(&seconds) {
+            return Err(format!(
+                "expiry_grace_seconds must be between 0 and {}",
+                MAX_LINK_LIFETIME_SECONDS
+            ));
+        }
+    }
+
+    let update = database::UrlUpdate {
+        original_url,
+        expires_at: req.expires_at,
+        expiry_grace_seconds: req.expiry_grace_seconds,
+    };
+    if update.is_empty() {
+        return Err("No fields to update".to_string());
+    }
+
+    Ok(update)
+}
+
+// PATCH /urls/{id} endpoint - change any subset of a link's mutable fields in one update
+async fn update_url(
+    path: web::Path<String>,
+    req: web::Json<UpdateUrlRequest>,
+    session: Session,
+    db_pool: AppDatabasePool,
+    auth_config: web::Data<AuthConfig>,
+    expiry_config: web::Data<ExpiryConfig>,
+) -> Result<HttpResponse> {
+    let short_id = path.into_inner();
+
+    let entry = match load_managed_link(&session, &db_pool, &auth_config, &short_id).await {
+        Ok(link) => link.entry,
+        Err(response) => return Ok(response),
+    };
+
+    let update = match validate_url_update(req.into_inner(), chrono::Utc::now()) {
+        Ok(update) => update,
+        Err(error) => {
+            info!("Rejected update for short ID {}: {}", short_id, error);
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse { error }));
+        }
+    };
+
+    match DatabaseService::update_url(&db_pool, entry.id, &update).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return Ok(HttpResponse::NotFound().json(ErrorResponse {
+                error: "Short URL not found".to_string(),
+            }));
+        }
+        Err(e) => {
+            error!("Failed to update URL {}: {}", short_id, e);
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to update URL".to_string(),
+            }));
+        }
+    }
+
+    info!("Updated short ID {}", short_id);
+
+    match DatabaseService::get_url_by_short_id(&db_pool, &short_id).await {
+        Ok(Some(entry)) => {
+            let tags = match link_tags(&db_pool, &entry).await {
+                Ok(tags) => tags,
+                Err(response) => return Ok(response),
+            };
+            Ok(HttpResponse::Ok().json(link_config_response(entry, &expiry_config, tags, None)))
+        }
+        Ok(None) => Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "Short URL not found".to_string(),
+        })),
+        Err(e) => {
+            error!("Database error retrieving URL for {}: {}", short_id, e);
+            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Database error".to_string(),
+            }))
+        }
+    }
+}
+
+// POST /urls/{id}/renew endpoint - push back the expiry of one of the signed-in user's links
+async fn renew_url(
+    path: web::Path<String>,
+    req: web::Json<RenewUrlRequest>,
+    session: Session,
+    db_pool: AppDatabasePool,
+    expiry_config: web::Data<ExpiryConfig>,
+) -> Result<HttpResponse> {
+    let short_id = path.into_inner();
+
+    let user_id = match session.get::<i64>("user_id")? {
+        Some(user_id) => user_id,
+        None => {
+            return Ok(HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "Not authenticated".to_string(),
+            }));
+        }
+    };
+
+    if !(1..=MAX_LINK_LIFETIME_SECONDS).contains(&req.expires_in_seconds) {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!(
+                "expires_in_seconds must be between 1 and {}",
+                MAX_LINK_LIFETIME_SECONDS
+            ),
+        }));
+    }
+
+    let entry = match DatabaseService::get_url_by_short_id(&db_pool, &short_id).await {
+        Ok(Some(entry)) if entry.user_id == Some(user_id) => entry,
+        Ok(_) => {
+            return Ok(HttpResponse::NotFound().json(ErrorResponse {
+                error: "Short URL not found".to_string(),
+            }));
+        }
+        Err(e) => {
+            error!("Database error retrieving URL for {}: {}", short_id, e);
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Database error".to_string(),
+            }));
+        }
+    };
+
+    let now = chrono::Utc::now();
+    let current_expiry = match entry.expires_at {
+        Some(expires_at) => expires_at,
+        None => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: "Short URL does not expire".to_string(),
+            }));
+        }
+    };
+
+    if expiry_config.state_of(&entry, now) == ExpiryState::Expired {
+        return Ok(HttpResponse::Gone().json(ErrorResponse {
+            error: "Short URL expired and its grace window has passed".to_string(),
+        }));
+    }
+
+    // Extend from the current expiry while the link is live, or from now once it has lapsed
+    let expires_at =
+        current_expiry.max(now) + chrono::Duration::seconds(req.expires_in_seconds);
+
+    match DatabaseService::update_url_expiry(&db_pool, entry.id, expires_at).await {
+        Ok(_) => {
+            info!("Renewed short ID {} until {}", short_id, expires_at);
+            Ok(HttpResponse::Ok().json(RenewUrlResponse {
+                short_id,
+                expires_at,
+            }))
+        }
+        Err(e) => {
+            error!("Failed to renew URL {}: {}", short_id, e);
+            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to renew URL".to_string(),
+            }))
+        }
+    }
+}
+
+// Status a redirect of the link would reveal
+fn short_id_status(
+    entry: Option<&database::UrlEntry>,
+    expiry_config: &ExpiryConfig,
+    now: chrono::DateTime<chrono::Utc>,
+) -> &'static str {
+    match entry {
+        None => "not_found",
+        Some(entry) => match expiry_config.state_of(entry, now) {
+            ExpiryState::Active => "active",
+            // Links in their grace window no longer redirect either
+            ExpiryState::InGrace | ExpiryState::Expired => "expired",
+        },
+    }
+}
+
+// POST /urls/status/batch endpoint - public status of many short IDs in one query, rate limited
+// to discourage enumeration
+async fn url_status_batch(
+    req: web::Json<StatusBatchRequest>,
+    http_req: HttpRequest,
+    session: Session,
+    db_pool: AppDatabasePool,
+    expiry_config: web::Data<ExpiryConfig>,
+    rate_limiter: web::Data<RateLimiter>,
+    batch_limits: web::Data<BatchLimits>,
+) -> Result<HttpResponse> {
+    if let Some(response) = rate_limit::enforce(
+        &rate_limiter,
+        RateLimitBucket::StatusBatch,
+        None,
+        &session,
+        &http_req,
+    ) {
+        return Ok(response);
+    }
+
+    let short_ids = req.into_inner().short_ids;
+    if short_ids.len() > batch_limits.status {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!(
+                "At most {} short IDs can be checked per request (MAX_BATCH_STATUS)",
+                batch_limits.status
+            ),
+        }));
+    }
+
+    let mut unique_ids = short_ids.clone();
+    unique_ids.sort();
+    unique_ids.dedup();
+
+    let entries = match DatabaseService::get_urls_by_short_ids(&db_pool, &unique_ids).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Database error checking short ID statuses: {}", e);
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Database error".to_string(),
+            }));
+        }
+    };
+    let entries: std::collections::HashMap<&str, &database::UrlEntry> = entries
+        .iter()
+        .map(|entry| (entry.shortened_url.as_str(), entry))
+        .collect();
+
+    let now = chrono::Utc::now();
+    let statuses = short_ids
+        .into_iter()
+        .map(|short_id| {
+            let status =
+                short_id_status(entries.get(short_id.as_str()).copied(), &expiry_config, now);
+            ShortIdStatus {
+                short_id,
+                status: status.to_string(),
+            }
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(StatusBatchResponse { statuses }))
+}
+
+// POST /admin/webhooks/test endpoint - admin only, send a signed sample event to the configured webhook URL
+async fn admin_test_webhook(
+    session: Session,
+    db_pool: AppDatabasePool,
+    auth_config: web::Data<AuthConfig>,
+    webhook_service: web::Data<WebhookService>,
+) -> Result<HttpResponse> {
+    let admin = match require_admin(&session, &db_pool, &auth_config).await {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
+
+    if !webhook_service.is_configured() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "No webhook URL configured. Set WEBHOOK_URL and WEBHOOK_SIGNING_SECRET"
+                .to_string(),
+        }));
+    }
+
+    let event = WebhookEvent::test(chrono::Utc::now());
+    let started = std::time::Instant::now();
+    let response = match webhook_service.deliver(&event).await {
+        Ok(delivery) => {
+            info!(
+                "Admin {} sent test webhook {}: receiver returned {} in {}ms",
+                admin.username,
+                event.id,
+                delivery.status,
+                delivery.elapsed.as_millis()
+            );
+            WebhookTestResponse {
+                event_id: event.id,
+                event_type: event.event_type,
+                delivered: (200..300).contains(&delivery.status),
+                status: Some(delivery.status),
+                response_time_ms: delivery.elapsed.as_millis() as u64,
+                error: None,
+            }
+        }
+        Err(e) => {
+            warn!("Test webhook {} failed: {}", event.id, e);
+            WebhookTestResponse {
+                event_id: event.id,
+                event_type: event.event_type,
+                delivered: false,
+                status: None,
+                response_time_ms: started.elapsed().as_millis() as u64,
+                error: Some(e.to_string()),
+            }
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// PATCH /admin/users/{id} endpoint - admin only, set a user's rate limit tier
+async fn admin_update_user(
+    path: web::Path<i64>,
+    req: web::Json<AdminUpdateUserRequest>,
+    session: Session,
+    db_pool: AppDatabasePool,
+    auth_config: web::Data<AuthConfig>,
+) -> Result<HttpResponse> {
+    let admin = match require_admin(&session, &db_pool, &auth_config).await {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
+    let user_id = path.into_inner();
+
+    let Some(tier) = RateLimitTier::parse(&req.rate_limit_tier) else {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!(
+                "Invalid rate_limit_tier '{}'. Expected one of: free, pro, enterprise",
+                req.rate_limit_tier
+            ),
+        }));
+    };
+
+    match DatabaseService::update_user_rate_limit_tier(&db_pool, user_id, tier.as_str(), admin.id)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            return Ok(HttpResponse::NotFound().json(ErrorResponse {
+                error: "User not found".to_string(),
+            }));
+        }
+        Err(e) => {
+            error!("Failed to update rate limit tier for user {}: {}", user_id, e);
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to update user".to_string(),
+            }));
+        }
+    }
+
+    info!(
+        "Admin {} set rate limit tier of user {} to {}",
+        admin.username,
+        user_id,
+        tier.as_str()
+    );
+
+    match DatabaseService::get_user_by_id(&db_pool, user_id).await {
+        Ok(Some(user)) => Ok(HttpResponse::Ok().json(AdminUserResponse {
+            id: user.id,
+            username: user.username,
+            rate_limit_tier: user.rate_limit_tier,
+        })),
+        Ok(None) => Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "User not found".to_string(),
+        })),
+        Err(e) => {
+            error!("Database error retrieving user {}: {}", user_id, e);
+            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Database error".to_string(),
+            }))
+        }
+    }
+}
+
+// GET /urls/{id}/stats/daily endpoint - a link's redirects per UTC day as JSON or CSV, for its owner or an admin
+async fn url_daily_stats(
+    path: web::Path<String>,
+    query: web::Query<DailyStatsQuery>,
+    http_req: HttpRequest,
+    session: Session,
+    db_pool: AppDatabasePool,
+    auth_config: web::Data<AuthConfig>,
+) -> Result<HttpResponse> {
+    let short_id = path.into_inner();
+
+    let format = match query.format.as_deref() {
+        Some(format) => format.to_lowercase(),
+        None => {
+            let accepts_csv = http_req
+                .headers()
+                .get(actix_web::http::header::ACCEPT)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|accept| accept.contains("text/csv"));
+            if accepts_csv { "csv" } else { "json" }.to_string()
+        }
+    };
+    if format != "json" && format != "csv" {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!(
+                "Unsupported stats format '{}'. Supported formats: json, csv",
+                format
+            ),
+        }));
+    }
+
+    let days = query.days.unwrap_or(30);
+    if !(1..=MAX_DAILY_STATS_DAYS).contains(&days) {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("days must be between 1 and {}", MAX_DAILY_STATS_DAYS),
+        }));
+    }
+
+    let link = match load_managed_link(&session, &db_pool, &auth_config, &short_id).await {
+        Ok(link) => link,
+        Err(response) => return Ok(response),
+    };
+    let entry = &link.entry;
+
+    let end = chrono::Utc::now().date_naive();
+    let start = end - chrono::Duration::days(days - 1);
+    let rows = match DatabaseService::get_daily_clicks(&db_pool, entry.id, start, end).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            error!("Failed to retrieve daily stats for {}: {}", short_id, e);
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve stats".to_string(),
+            }));
+        }
+    };
+    let counts = stats::fill_daily_counts(&rows, start, end);
+
+    if format == "csv" {
+        return Ok(HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .append_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"{}-daily-stats.csv\"", short_id),
+            ))
+            .body(stats::render_daily_counts_csv(&counts)));
+    }
+
+    let creator = match creator_for_admin(&db_pool, &link).await {
+        Ok(creator) => creator,
+        Err(response) => return Ok(response),
+    };
+
+    Ok(HttpResponse::Ok().json(DailyStatsResponse {
+        short_id,
+        days: counts,
+        creator,
+    }))
+}
+
+// GET /rate-limit endpoint - the caller's standing in every rate-limited bucket, without consuming any
+async fn rate_limit_status(
+    http_req: HttpRequest,
+    session: Session,
+    db_pool: AppDatabasePool,
+    rate_limiter: web::Data<RateLimiter>,
+) -> Result<HttpResponse> {
+    let rate_limit_tier = rate_limit::caller_tier(&session, &db_pool).await;
+    let now = chrono::Utc::now();
+    let buckets = RateLimitBucket::ALL
+        .iter()
+        .map(|bucket| {
+            let key = rate_limit::caller_key(&rate_limiter, *bucket, &session, &http_req);
+            rate_limiter.status(*bucket, &key, rate_limit_tier, now)
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(RateLimitStatusResponse { buckets }))
+}
+
+// GET /stats/media-types endpoint - count the caller's links per target media category
+async fn media_type_stats(session: Session, db_pool: AppDatabasePool) -> Result<HttpResponse> {
+    let Some(user_id) = session.get::<i64>("user_id")? else {
+        return Ok(HttpResponse::Unauthorized().json(ErrorResponse {
+            error: "Not authenticated".to_string(),
+        }));
+    };
+
+    match DatabaseService::get_media_type_counts(&db_pool, user_id).await {
+        Ok(rows) => {
+            // Report every known category so clients can render filters without guessing
+            let mut counts: std::collections::BTreeMap<String, i64> = MEDIA_TYPE_CATEGORIES
+                .iter()
+                .map(|category| (category.to_string(), 0))
+                .collect();
+            for (media_type, count) in rows {
+                counts.insert(media_type, count);
+            }
+
+            Ok(HttpResponse::Ok().json(MediaTypeStatsResponse { counts }))
+        }
+        Err(e) => {
+            error!("Failed to retrieve media type stats: {}", e);
+            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve media type stats".to_string(),
+            }))
+        }
+    }
+}
+
+// GET /urls/export endpoint - download the caller's links for import elsewhere
+async fn export_urls(
+    query: web::Query<ExportQuery>,
+    session: Session,
+    db_pool: AppDatabasePool,
+    export_signing: web::Data<ExportSigning>,
+) -> Result<HttpResponse> {
+    let user_id: i64 = match session.get("user_id")? {
+        Some(id) => id,
+        None => {
+            return Ok(HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "Not authenticated".to_string(),
+            }));
+        }
+    };
+
+    let format = query.format.as_deref().unwrap_or("netscape");
+    if !["netscape", "csv", "ndjson"].contains(&format) {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!(
+                "Unsupported export format '{}'. Supported formats: netscape, csv, ndjson",
+                format
+            ),
+        }));
+    }
+
+    let target = match query.link.as_deref().unwrap_or("short") {
+        "short" => BookmarkTarget::ShortUrl,
+        "target" => BookmarkTarget::OriginalUrl,
+        other => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: format!("Unsupported link option '{}'. Use 'short' or 'target'", other),
+            }));
+        }
+    };
+
+    let media_type = match parse_media_type_filter(query.media_type.as_deref()) {
+        Ok(media_type) => media_type,
+        Err(error) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse { error }));
+        }
+    };
+
+    // Signed exports need the server secret; refuse rather than return an unsigned file
+    let signing_secret = if query.sign.unwrap_or(false) {
+        match export_signing.secret() {
+            Some(secret) => Some(secret),
+            None => {
+                error!("Signed export requested but EXPORT_SIGNING_SECRET is not set");
+                return Ok(HttpResponse::ServiceUnavailable().json(ErrorResponse {
+                    error: "Signed exports are not configured on this server".to_string(),
+                }));
+            }
+        }
+    } else {
+        None
+    };
+
+    info!("Exporting links for user ID {} as {}", user_id, format);
+
+    let mut links = match DatabaseService::get_urls_by_user(&db_pool, user_id).await {
+        Ok(links) => links,
+        Err(e) => {
+            error!("Failed to retrieve links for export: {}", e);
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve links".to_string(),
+            }));
+        }
+    };
+    if let Some(media_type) = media_type {
+        links.retain(|link| link.media_type.as_deref() == Some(media_type));
+    }
+
+    // Only the bookmark format groups links by tag
+    let tags = if format == "netscape" {
+        match DatabaseService::get_link_tags_by_user(&db_pool, user_id).await {
+            Ok(tags) => tags,
+            Err(e) => {
+                error!("Failed to retrieve link tags for export: {}", e);
+                return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Failed to retrieve links".to_string(),
+                }));
+            }
+        }
+    } else {
+        std::collections::HashMap::new()
+    };
+
+    let (body, content_type, filename) = match format {
+        "csv" => (render_csv(&links), "text/csv; charset=utf-8", "thalora-links.csv"),
+        "ndjson" => (
+            render_ndjson(&links),
+            "application/x-ndjson",
+            "thalora-links.ndjson",
+        ),
+        _ => (
+            render_netscape_bookmarks(&links, &tags, target),
+            "text/html; charset=utf-8",
+            "thalora-bookmarks.html",
+        ),
+    };
+
+    let mut response = HttpResponse::Ok();
+    response.content_type(content_type).append_header((
+        "Content-Disposition",
+        format!("attachment; filename=\"{}\"", filename),
+    ));
+
+    if let Some(secret) = signing_secret {
+        let manifest = ExportManifest::sign(
+            secret,
+            format,
+            body.as_bytes(),
+            links.len(),
+            &chrono::Utc::now().to_rfc3339(),
+        );
+        response
+            .append_header(("X-Export-Format", manifest.format))
+            .append_header(("X-Export-Row-Count", manifest.row_count.to_string()))
+            .append_header(("X-Export-Content-SHA256", manifest.content_sha256))
+            .append_header(("X-Export-Generated-At", manifest.generated_at))
+            .append_header(("X-Export-Signature", manifest.signature));
+    }
+
+    Ok(response.body(body))
+}
+
+// POST /urls/import endpoint - create the signed-in user's links from a CSV upload. Rows with an
+// invalid URL are skipped and invalid tags are left off; both are reported in the summary. All
+// imported links and their tags are stored in one transaction.
+#[allow(clippy::too_many_arguments)] // one parameter per actix extractor
+async fn import_urls(
+    body: String,
+    http_req: HttpRequest,
+    session: Session,
+    db_pool: AppDatabasePool,
+    default_domain_strategy: web::Data<DefaultDomainStrategy>,
+    short_link_scheme: web::Data<ShortLinkScheme>,
+    domain_verification: web::Data<DomainVerification>,
+    rate_limiter: web::Data<RateLimiter>,
+    batch_limits: web::Data<BatchLimits>,
+) -> Result<HttpResponse> {
+    let Some(user_id) = session.get::<i64>("user_id")? else {
+        return Ok(HttpResponse::Unauthorized().json(ErrorResponse {
+            error: "Not authenticated".to_string(),
+        }));
+    };
+
+    let rows = match parse_import_csv(&body) {
+        Ok(rows) => rows,
+        Err(error) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse { error }));
+        }
+    };
+    if rows.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "The CSV file has no links to import".to_string(),
+        }));
+    }
+    if rows.len() > batch_limits.import {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!(
+                "At most {} links can be imported per request (MAX_BATCH_IMPORT)",
+                batch_limits.import
+            ),
+        }));
+    }
+
+    // Each row counts against the shorten limit, as if its link were created on its own
+    let rate_limit_tier = rate_limit::caller_tier(&session, &db_pool).await;
+    if let Some(response) = rate_limit::enforce_n(
+        &rate_limiter,
+        RateLimitBucket::Shorten,
+        rate_limit_tier,
+        rows.len() as u32,
+        &session,
+        &http_req,
+    ) {
+        return Ok(response);
+    }
+
+    let mut errors = Vec::new();
+    let mut accepted = Vec::new();
+    let mut skipped = 0;
+    for row in rows {
+        let request = ShortenRequest {
+            url: row.original_url,
+            domain: None,
+            expires_in_seconds: None,
+            expiry_grace_seconds: None,
+        };
+        if let Err(error) = validate_new_link(&request) {
+            errors.push(ImportRowError {
+                line: row.line,
+                error,
+            });
+            skipped += 1;
+            continue;
+        }
+
+        let (tags, tag_errors) = parse_tag_list(&row.tags);
+        errors.extend(tag_errors.into_iter().map(|error| ImportRowError {
+            line: row.line,
+            error,
+        }));
+        accepted.push((row.line, request.url.trim().to_string(), tags));
+    }
+
+    let mut links = Vec::with_capacity(accepted.len());
+    if !accepted.is_empty() {
+        let base_url = match resolve_request_base_url(
+            &http_req,
+            &db_pool,
+            Some(user_id),
+            None,
+            &default_domain_strategy,
+            &short_link_scheme,
+            &domain_verification,
+        )
+        .await
+        {
+            Ok(resolved) => resolved.base_url,
+            Err(response) => return Ok(response),
+        };
+
+        let mut reserved = std::collections::HashSet::new();
+        let mut new_urls = Vec::with_capacity(accepted.len());
+        for (_, original_url, tags) in &accepted {
+            let short_id = match generate_unique_short_id(&db_pool, &reserved).await {
+                Ok(short_id) => short_id,
+                Err(e) => {
+                    error!("Database error checking URL existence: {}", e);
+                    return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                        error: "Database error".to_string(),
                     }));
                 }
-            }
+            };
+            reserved.insert(short_id.clone());
+
+            new_urls.push(database::NewUrl {
+                original_url: original_url.clone(),
+                shortened_url: short_id,
+                base_url: base_url.clone(),
+                expires_at: None,
+                expiry_grace_seconds: None,
+                tags: tags.clone(),
+            });
         }
-        Err(e) => {
-            error!("Failed to retrieve domains: {}", e);
+
+        if let Err(e) = DatabaseService::insert_urls(&db_pool, user_id, &new_urls).await {
+            error!("Failed to store imported URLs in database: {}", e);
             return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: "Failed to retrieve domain information".to_string(),
+                error: "Failed to store URLs".to_string(),
             }));
         }
-    };
 
-    // Return the shortened URL
-    Ok(HttpResponse::Ok().json(ShortenResponse {
-        short_url: format!("{}/shortened-url/{}", base_url, short_id),
-        original_url: original_url.to_string(),
+        links = new_urls
+            .into_iter()
+            .zip(&accepted)
+            .map(|(url, (line, _, _))| ImportedLink {
+                line: *line,
+                short_url: format!("{}/shortened-url/{}", url.base_url, url.shortened_url),
+                original_url: url.original_url,
+                tags: url.tags,
+            })
+            .collect();
+    }
+
+    info!(
+        "Imported {} links for user {} ({} rows skipped, {} errors)",
+        links.len(),
+        user_id,
+        skipped,
+        errors.len()
+    );
+
+    Ok(HttpResponse::Ok().json(ImportResponse {
+        imported: links.len(),
+        skipped,
+        links,
+        errors,
     }))
 }
 
-// GET /shortened-url/{id} endpoint
-async fn redirect_url(path: web::Path<String>, db_pool: AppDatabasePool) -> Result<HttpResponse> {
-    let short_id = path.into_inner();
-
-    info!("Received redirect request for short ID: {short_id}");
-
-    // Look up the original URL in the database using the pool
-    let original_url = match DatabaseService::get_original_url(&db_pool, &short_id).await {
-        Ok(url) => url,
-        Err(e) => {
-            error!("Database error retrieving URL for {}: {}", short_id, e);
-            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: "Database error".to_string(),
+// POST /exports/verify endpoint - check an export body against its X-Export-* headers
+// Bodies are capped at MAX_VERIFY_EXPORT_BYTES by the route's PayloadConfig
+async fn verify_export(
+    http_req: HttpRequest,
+    body: web::Bytes,
+    export_signing: web::Data<ExportSigning>,
+) -> Result<HttpResponse> {
+    let secret = match export_signing.secret() {
+        Some(secret) => secret,
+        None => {
+            return Ok(HttpResponse::ServiceUnavailable().json(ErrorResponse {
+                error: "Signed exports are not configured on this server".to_string(),
             }));
         }
     };
 
-    match original_url {
-        Some(url) => {
-            info!("Redirecting {short_id} to {url}");
-            Ok(HttpResponse::Found()
-                .append_header(("Location", url))
-                .finish())
+    let header = |name: &str| {
+        http_req
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string())
+    };
+
+    let manifest = match (
+        header("X-Export-Format"),
+        header("X-Export-Row-Count").and_then(|count| count.parse().ok()),
+        header("X-Export-Content-SHA256"),
+        header("X-Export-Generated-At"),
+        header("X-Export-Signature"),
+    ) {
+        (Some(format), Some(row_count), Some(content_sha256), Some(generated_at), Some(signature)) => {
+            ExportManifest {
+                format,
+                row_count,
+                content_sha256,
+                generated_at,
+                signature,
+            }
         }
-        None => {
-            info!("Short ID not found: {short_id}");
-            Ok(HttpResponse::NotFound().json(ErrorResponse {
-                error: "Short URL not found".to_string(),
-            }))
+        _ => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: "Missing or invalid X-Export-Format, X-Export-Row-Count, X-Export-Content-SHA256, X-Export-Generated-At or X-Export-Signature header".to_string(),
+            }));
         }
-    }
+    };
+
+    let valid = manifest.verify(secret, &body);
+    info!(
+        "Verified {} export with {} rows: valid = {}",
+        manifest.format, manifest.row_count, valid
+    );
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "valid": valid,
+        "row_count": manifest.row_count,
+    })))
 }
 
 // Health check endpoint
@@ -371,9 +2177,10 @@ async fn health_check() -> Result<HttpResponse> {
 // POST /domains endpoint - add a custom domain
 async fn add_domain(
     req: web::Json<AddDomainRequest>,
+    session: Session,
     db_pool: AppDatabasePool,
 ) -> Result<HttpResponse> {
-    let domain_name = req.domain_name.trim().to_lowercase();
+    let domain_name = normalize_domain(&req.domain_name);
 
     info!("Received add domain request for: {}", domain_name);
 
@@ -406,11 +2213,12 @@ async fn add_domain(
     let (is_verified, verification_message, verification_token) =
         DomainValidationService::validate_domain(&domain_name).await;
 
-    // Store the domain in the database
+    // Store the domain in the database, owned by the signed-in user if there is one
+    let user_id = session.get::<i64>("user_id")?;
     match DatabaseService::insert_domain(
         &db_pool,
         &domain_name,
-        None,
+        user_id,
         is_verified,
         verification_token.clone(),
     )
@@ -438,6 +2246,50 @@ async fn add_domain(
     }
 }
 
+// POST /domains/validate endpoint - normalize and check a domain without adding it
+async fn validate_new_domain(
+    req: web::Json<AddDomainRequest>,
+    db_pool: AppDatabasePool,
+) -> Result<HttpResponse> {
+    let normalized = normalize_domain(&req.domain_name);
+
+    info!(
+        "Received validate domain request for: '{}' (normalized: '{}')",
+        req.domain_name, normalized
+    );
+
+    let format_check = DomainValidationService::check_domain_format(&normalized);
+
+    // Only look up names that could actually be added
+    let is_taken = if format_check.is_ok() {
+        match DatabaseService::get_domain_by_name(&db_pool, &normalized).await {
+            Ok(existing) => existing.is_some(),
+            Err(e) => {
+                error!("Database error checking domain existence: {}", e);
+                return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Database error".to_string(),
+                }));
+            }
+        }
+    } else {
+        false
+    };
+
+    let message = match &format_check {
+        Err(message) => Some(message.clone()),
+        Ok(()) if is_taken => Some("Domain already exists".to_string()),
+        Ok(()) => None,
+    };
+
+    Ok(HttpResponse::Ok().json(ValidateDomainResponse {
+        domain_name: req.domain_name.clone(),
+        normalized_domain_name: normalized,
+        is_valid: format_check.is_ok(),
+        is_taken,
+        message,
+    }))
+}
+
 // GET /domains endpoint - list all domains
 async fn list_domains(db_pool: AppDatabasePool) -> Result<HttpResponse> {
     match DatabaseService::get_all_domains(&db_pool).await {
@@ -454,8 +2306,94 @@ async fn list_domains(db_pool: AppDatabasePool) -> Result<HttpResponse> {
     }
 }
 
+// POST /domains/{id}/default endpoint - make a verified domain the signed-in user's default
+async fn set_default_domain(
+    path: web::Path<i64>,
+    session: Session,
+    db_pool: AppDatabasePool,
+    auth_config: web::Data<AuthConfig>,
+) -> Result<HttpResponse> {
+    let domain_id = path.into_inner();
+
+    let user_id = match session.get::<i64>("user_id")? {
+        Some(user_id) => user_id,
+        None => {
+            return Ok(HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "Not authenticated".to_string(),
+            }));
+        }
+    };
+
+    info!(
+        "Received set default domain request for ID: {} from user {}",
+        domain_id, user_id
+    );
+
+    let domain = match DatabaseService::get_domain_by_id(&db_pool, domain_id).await {
+        Ok(Some(domain)) if domain.user_id == Some(user_id) || domain.user_id.is_none() => domain,
+        Ok(_) => {
+            return Ok(HttpResponse::NotFound().json(ErrorResponse {
+                error: "Domain not found".to_string(),
+            }));
+        }
+        Err(e) => {
+            error!("Database error retrieving domain: {}", e);
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Database error".to_string(),
+            }));
+        }
+    };
+
+    // Shared domains have no owner; only admins choose the default among them
+    if domain.user_id.is_none() {
+        if let Err(response) = require_admin(&session, &db_pool, &auth_config).await {
+            return Ok(response);
+        }
+    }
+
+    if !domain.is_verified {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "Only verified domains can be the default".to_string(),
+        }));
+    }
+
+    match DatabaseService::set_default_domain(&db_pool, domain_id, domain.user_id).await {
+        Ok(true) => {
+            match domain.user_id {
+                Some(owner) => info!(
+                    "Domain '{}' is now the default for user {}",
+                    domain.domain_name, owner
+                ),
+                None => info!(
+                    "Admin {} made '{}' the default shared domain",
+                    user_id, domain.domain_name
+                ),
+            }
+            Ok(HttpResponse::Ok().json(SetDefaultDomainResponse {
+                id: domain.id,
+                domain_name: domain.domain_name,
+                is_default: true,
+            }))
+        }
+        // The domain was removed or unverified after it was read
+        Ok(false) => Ok(HttpResponse::Conflict().json(ErrorResponse {
+            error: "Domain is no longer available to be the default".to_string(),
+        })),
+        Err(e) => {
+            error!("Failed to set default domain: {}", e);
+            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to set default domain".to_string(),
+            }))
+        }
+    }
+}
+
 // POST /domains/{id}/verify endpoint - verify a domain by checking DNS TXT record
-async fn verify_domain(path: web::Path<i64>, db_pool: AppDatabasePool) -> Result<HttpResponse> {
+async fn verify_domain(
+    path: web::Path<i64>,
+    db_pool: AppDatabasePool,
+    domain_verification: web::Data<DomainVerification>,
+) -> Result<HttpResponse> {
     let domain_id = path.into_inner();
 
     info!("Received domain verification request for ID: {}", domain_id);
@@ -496,9 +2434,12 @@ async fn verify_domain(path: web::Path<i64>, db_pool: AppDatabasePool) -> Result
     };
 
     // Verify the DNS TXT record
-    let is_verified =
-        DomainValidationService::verify_dns_txt_record(&domain.domain_name, &verification_token)
-            .await;
+    let is_verified = DomainValidationService::verify_dns_txt_record(
+        &domain.domain_name,
+        &verification_token,
+        &domain_verification,
+    )
+    .await;
 
     if is_verified {
         // Update domain as verified in database
@@ -529,6 +2470,85 @@ async fn verify_domain(path: web::Path<i64>, db_pool: AppDatabasePool) -> Result
     }
 }
 
+// POST /admin/domains/regenerate-tokens endpoint - issue new verification tokens and require re-verification
+async fn regenerate_domain_tokens(
+    req: web::Json<RegenerateTokensRequest>,
+    session: Session,
+    db_pool: AppDatabasePool,
+    auth_config: web::Data<AuthConfig>,
+) -> Result<HttpResponse> {
+    let admin = match require_admin(&session, &db_pool, &auth_config).await {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let all_domains = match DatabaseService::get_all_domains(&db_pool).await {
+        Ok(domains) => domains,
+        Err(e) => {
+            error!("Failed to retrieve domains: {}", e);
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve domains".to_string(),
+            }));
+        }
+    };
+
+    let (domains, not_found) = match &req.domain_ids {
+        Some(domain_ids) => {
+            let domains: Vec<_> = all_domains
+                .into_iter()
+                .filter(|domain| domain_ids.contains(&domain.id))
+                .collect();
+            let not_found = domain_ids
+                .iter()
+                .filter(|id| !domains.iter().any(|domain| domain.id == **id))
+                .copied()
+                .collect();
+            (domains, not_found)
+        }
+        None => (all_domains, Vec::new()),
+    };
+
+    let new_tokens: Vec<(i64, String)> = domains
+        .iter()
+        .map(|domain| (domain.id, DomainValidationService::generate_verification_token()))
+        .collect();
+
+    warn!(
+        "Admin {} (ID: {}) is regenerating verification tokens for {} domains",
+        admin.username,
+        admin.id,
+        new_tokens.len()
+    );
+
+    if let Err(e) = DatabaseService::regenerate_domain_tokens(&db_pool, &new_tokens, admin.id).await
+    {
+        error!("Failed to regenerate domain verification tokens: {}", e);
+        return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to regenerate verification tokens".to_string(),
+        }));
+    }
+
+    let regenerated = domains
+        .into_iter()
+        .zip(new_tokens)
+        .map(|(domain, (_, token))| RegeneratedTokenResponse {
+            id: domain.id,
+            txt_record_name: format!("_thalora-verification.{}", domain.domain_name),
+            verification_status: format!(
+                "Domain verification reset. Please update the TXT record: _thalora-verification.{} with value: {}",
+                domain.domain_name, token
+            ),
+            domain_name: domain.domain_name,
+            verification_token: token,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(RegenerateTokensResponse {
+        regenerated,
+        not_found,
+    }))
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // Load environment variables from .env file if it exists
@@ -539,8 +2559,33 @@ async fn main() -> std::io::Result<()> {
 
     info!("Starting Thalora URL Shortener Backend");
 
+    // Merge settings from CONFIG_FILE underneath the environment; every component below is
+    // configured from the merged settings
+    let app_config = match config::AppConfig::from_env() {
+        Ok(app_config) => app_config,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(file) = &app_config.file {
+        info!(
+            "Loaded settings from CONFIG_FILE '{}' (environment variables take precedence): {}",
+            file,
+            app_config.file_settings.join(", ")
+        );
+    }
+    info!(
+        "Batch limits per request: shorten {}, status {}, import {}",
+        app_config.batch_limits.shorten,
+        app_config.batch_limits.status,
+        app_config.batch_limits.import
+    );
+    let batch_limits = web::Data::new(app_config.batch_limits);
+    let settings = app_config.settings;
+
     // Initialize database configuration
-    let db_config = match DatabaseConfig::from_env() {
+    let db_config = match DatabaseConfig::from_env(&settings) {
         Ok(config) => config,
         Err(e) => {
             error!("Failed to load database configuration: {}", e);
@@ -570,21 +2615,118 @@ async fn main() -> std::io::Result<()> {
     info!("Database connection pool established successfully");
 
     // Get server configuration from environment or use defaults
-    let host = std::env::var("SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
-    let port = std::env::var("SERVER_PORT")
-        .ok()
+    let host = settings
+        .get("SERVER_HOST")
+        .unwrap_or_else(|| "127.0.0.1".to_string());
+    let port = settings
+        .get("SERVER_PORT")
         .and_then(|p| p.parse().ok())
         .unwrap_or(8080);
 
     let bind_address = format!("{}:{}", host, port);
     info!("Server will bind to: {}", bind_address);
 
+    // Strategy for choosing the default domain when a shorten request names none
+    let default_domain_strategy = match DefaultDomainStrategy::from_env(&settings) {
+        Ok(strategy) => strategy,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    info!("Default domain strategy: {}", default_domain_strategy.as_str());
+    let default_domain_strategy = web::Data::new(default_domain_strategy);
+
+    // Scheme for short links built on custom domains
+    let short_link_scheme = match ShortLinkScheme::from_env(&settings) {
+        Ok(scheme) => scheme,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    info!("Short link scheme: {}", short_link_scheme.0);
+    if short_link_scheme.0 == "http" {
+        let is_production = settings
+            .get("ENVIRONMENT")
+            .unwrap_or_else(|| "development".to_string())
+            .to_lowercase()
+            == "production";
+        if is_production {
+            warn!("SHORT_LINK_SCHEME=http in production: short links will be issued over plain HTTP. Only use this for intranet deployments.");
+        }
+    }
+    let short_link_scheme = web::Data::new(short_link_scheme);
+
+    // Grace window for expired links
+    let expiry_config = match ExpiryConfig::from_env(&settings) {
+        Ok(config) => web::Data::new(config),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Per-caller request budgets for shorten and login
+    let rate_limiter = match RateLimiter::from_env(&settings) {
+        Ok(rate_limiter) => web::Data::new(rate_limiter),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let slug_matching = SlugMatching::from_env(&settings);
+    info!("Tolerate slug suffix: {}", slug_matching.tolerate_suffix);
+    let slug_matching = web::Data::new(slug_matching);
+
+    let domain_verification = DomainVerification::from_env(&settings);
+    info!("Skip domain verification: {}", domain_verification.skip);
+    let domain_verification = web::Data::new(domain_verification);
+
+    let auth_config = web::Data::new(AuthConfig::from_env(&settings));
+    let export_signing = web::Data::new(ExportSigning::from_env(&settings));
+
+    // Shared HTTP client for outbound requests
+    let http_client = match reqwest::Client::builder()
+        .user_agent("thalora-backend")
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Failed to create HTTP client: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let media_type_service = match MediaTypeService::from_env(&settings) {
+        Ok(service) => web::Data::new(service),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let safety_check_service = match SafetyCheckService::from_env(&settings, http_client.clone()) {
+        Ok(service) => web::Data::new(service),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let webhook_service = match WebhookService::from_env(&settings, http_client) {
+        Ok(service) => web::Data::new(service),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
     // Generate a secure random key for session cookies
     let secret_key = Key::generate();
 
     // Get CORS configuration
-    let allowed_origins = std::env::var("ALLOWED_ORIGINS")
-        .unwrap_or_else(|_| "http://localhost:3000".to_string())
+    let allowed_origins = settings
+        .get("ALLOWED_ORIGINS")
+        .unwrap_or_else(|| "http://localhost:3000".to_string())
         .split(',')
         .map(|s| s.trim().to_string())
         .collect::<Vec<String>>();
@@ -592,8 +2734,13 @@ async fn main() -> std::io::Result<()> {
     // Start HTTP server
     HttpServer::new(move || {
         let mut cors = Cors::default()
-            .allowed_methods(vec!["GET", "POST", "OPTIONS"]) // Add OPTIONS for preflight
-            .allowed_headers(vec!["content-type", "accept", "origin", "x-requested-with"])
+            .allowed_methods(vec!["GET", "POST", "PATCH", "OPTIONS"]) // Add OPTIONS for preflight
+            .allowed_headers(
+                ["content-type", "accept", "origin", "x-requested-with"]
+                    .into_iter()
+                    .chain(EXPORT_MANIFEST_HEADERS),
+            )
+            .expose_headers(EXPORT_MANIFEST_HEADERS)
             .supports_credentials() // Required for session cookies
             .max_age(3600);
 
@@ -613,6 +2760,18 @@ async fn main() -> std::io::Result<()> {
 
         App::new()
             .app_data(web::Data::new(db_pool.clone()))
+            .app_data(media_type_service.clone())
+            .app_data(safety_check_service.clone())
+            .app_data(webhook_service.clone())
+            .app_data(default_domain_strategy.clone())
+            .app_data(short_link_scheme.clone())
+            .app_data(expiry_config.clone())
+            .app_data(slug_matching.clone())
+            .app_data(rate_limiter.clone())
+            .app_data(batch_limits.clone())
+            .app_data(export_signing.clone())
+            .app_data(auth_config.clone())
+            .app_data(domain_verification.clone())
             .wrap(cors)
             .wrap(session_middleware)
             .wrap(Logger::default())
@@ -634,9 +2793,40 @@ async fn main() -> std::io::Result<()> {
             .service(
                 web::scope("/api")
                     .route("/shorten", web::post().to(shorten_url))
+                    .route(
+                        "/shorten/batch-with-defaults",
+                        web::post().to(shorten_batch_with_defaults),
+                    )
+                    .route("/shorten/resolve-domain", web::get().to(resolve_domain))
+                    .route("/rate-limit", web::get().to(rate_limit_status))
+                    .route("/urls/export", web::get().to(export_urls))
+                    .service(
+                        web::resource("/urls/import")
+                            .app_data(web::PayloadConfig::new(MAX_IMPORT_BYTES))
+                            .route(web::post().to(import_urls)),
+                    )
+                    .route("/urls/status/batch", web::post().to(url_status_batch))
+                    .route("/urls/{id}", web::patch().to(update_url))
+                    .route("/urls/{id}/config", web::get().to(get_url_config))
+                    .route("/urls/{id}/stats/daily", web::get().to(url_daily_stats))
+                    .route("/urls/{id}/renew", web::post().to(renew_url))
+                    .service(
+                        web::resource("/exports/verify")
+                            .app_data(web::PayloadConfig::new(MAX_VERIFY_EXPORT_BYTES))
+                            .route(web::post().to(verify_export)),
+                    )
                     .route("/domains", web::post().to(add_domain))
                     .route("/domains", web::get().to(list_domains))
-                    .route("/domains/{id}/verify", web::post().to(verify_domain)),
+                    .route("/domains/validate", web::post().to(validate_new_domain))
+                    .route("/domains/{id}/verify", web::post().to(verify_domain))
+                    .route("/domains/{id}/default", web::post().to(set_default_domain))
+                    .route("/stats/media-types", web::get().to(media_type_stats))
+                    .route(
+                        "/admin/domains/regenerate-tokens",
+                        web::post().to(regenerate_domain_tokens),
+                    )
+                    .route("/admin/users/{id}", web::patch().to(admin_update_user))
+                    .route("/admin/webhooks/test", web::post().to(admin_test_webhook)),
             )
     })
     .bind(&bind_address)?
@@ -724,6 +2914,269 @@ mod tests {
         assert!(!is_valid_url("http://127.0.0.1:8080"));
     }
 
+    #[test]
+    fn test_short_link_scheme_parse() {
+        assert_eq!(ShortLinkScheme::parse("https").unwrap().0, "https");
+        assert_eq!(ShortLinkScheme::parse("HTTP").unwrap().0, "http");
+        assert_eq!(ShortLinkScheme::parse(" https ").unwrap().0, "https");
+        assert!(ShortLinkScheme::parse("ftp").is_none());
+        assert!(ShortLinkScheme::parse("").is_none());
+    }
+
+    #[test]
+    fn test_normalized_domain_format() {
+        let check =
+            |input: &str| DomainValidationService::check_domain_format(&normalize_domain(input));
+
+        assert!(check("HTTPS://Example.com/").is_ok());
+        assert!(check("https://münchen.de/path").is_ok());
+        assert!(check("https://").is_err());
+        assert!(check("invalid..domain").is_err());
+        assert!(check("https://exa mple.com").is_err());
+    }
+
+    #[test]
+    fn test_slug_prefix_with_trailing_data() {
+        let tolerant = SlugMatching {
+            tolerate_suffix: true,
+        };
+
+        assert_eq!(tolerant.prefix_slug("abc12345extrajunk"), Some("abc12345"));
+        assert_eq!(tolerant.prefix_slug("abc12345)."), Some("abc12345"));
+        // Exact-length and shorter paths are looked up as-is
+        assert_eq!(tolerant.prefix_slug("abc12345"), None);
+        assert_eq!(tolerant.prefix_slug("abc"), None);
+        // The prefix itself must be a well-formed short ID
+        assert_eq!(tolerant.prefix_slug("abc.2345extra"), None);
+        assert_eq!(tolerant.prefix_slug("abcdéfghij"), None);
+
+        let strict = SlugMatching {
+            tolerate_suffix: false,
+        };
+        assert_eq!(strict.prefix_slug("abc12345extrajunk"), None);
+    }
+
+    #[test]
+    fn test_parse_media_type_filter() {
+        assert_eq!(parse_media_type_filter(None), Ok(None));
+        assert_eq!(parse_media_type_filter(Some(" ")), Ok(None));
+        assert_eq!(parse_media_type_filter(Some("video")), Ok(Some("video")));
+        assert_eq!(parse_media_type_filter(Some(" Image ")), Ok(Some("image")));
+        assert!(parse_media_type_filter(Some("movies")).is_err());
+    }
+
+    #[test]
+    fn test_link_config_creator_only_when_provided() {
+        let now = chrono::Utc::now();
+        let expiry_config = ExpiryConfig {
+            grace_period: chrono::Duration::zero(),
+        };
+        let entry = || database::UrlEntry {
+            id: 1,
+            user_id: None,
+            original_url: "https://example.com".to_string(),
+            shortened_url: "abc12345".to_string(),
+            base_url: None,
+            media_type: None,
+            expires_at: None,
+            expiry_grace_seconds: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        let owner_view = serde_json::to_value(link_config_response(
+            entry(),
+            &expiry_config,
+            vec!["launch".to_string()],
+            None,
+        ))
+        .unwrap();
+        assert!(owner_view.get("creator").is_none());
+        assert_eq!(owner_view["tags"], serde_json::json!(["launch"]));
+
+        let creator = database::UrlCreator {
+            user_id: None,
+            username: None,
+            ip: Some("203.0.113.7".to_string()),
+        };
+        let admin_view = serde_json::to_value(link_config_response(
+            entry(),
+            &expiry_config,
+            Vec::new(),
+            Some(creator),
+        ))
+        .unwrap();
+        assert_eq!(admin_view["creator"]["ip"], "203.0.113.7");
+        assert!(admin_view["creator"]["user_id"].is_null());
+    }
+
+    #[test]
+    fn test_short_id_status() {
+        let now = chrono::Utc::now();
+        let expiry_config = ExpiryConfig {
+            grace_period: chrono::Duration::hours(1),
+        };
+        let entry = |expires_at: Option<chrono::DateTime<chrono::Utc>>| database::UrlEntry {
+            id: 1,
+            user_id: None,
+            original_url: "https://example.com".to_string(),
+            shortened_url: "abc12345".to_string(),
+            base_url: None,
+            media_type: None,
+            expires_at,
+            expiry_grace_seconds: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        assert_eq!(short_id_status(None, &expiry_config, now), "not_found");
+        assert_eq!(
+            short_id_status(Some(&entry(None)), &expiry_config, now),
+            "active"
+        );
+        assert_eq!(
+            short_id_status(
+                Some(&entry(Some(now + chrono::Duration::days(1)))),
+                &expiry_config,
+                now
+            ),
+            "active"
+        );
+        assert_eq!(
+            short_id_status(
+                Some(&entry(Some(now - chrono::Duration::minutes(1)))),
+                &expiry_config,
+                now
+            ),
+            "expired"
+        );
+        assert_eq!(
+            short_id_status(
+                Some(&entry(Some(now - chrono::Duration::days(1)))),
+                &expiry_config,
+                now
+            ),
+            "expired"
+        );
+    }
+
+    #[test]
+    fn test_batch_shorten_items_inherit_defaults() {
+        let req: BatchShortenRequest = serde_json::from_str(
+            r#"{
+                "defaults": {"domain": "go.example.com", "expires_in_seconds": 3600, "tags": ["launch"]},
+                "urls": [
+                    "https://example.com/a",
+                    {"url": "https://example.com/b", "domain": "links.example.com"},
+                    {"url": "https://example.com/c", "expires_in_seconds": 60, "expiry_grace_seconds": 30, "tags": []}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let (requests, tags): (Vec<ShortenRequest>, Vec<Vec<String>>) = req
+            .urls
+            .into_iter()
+            .map(|item| BatchShortenItem::parse(item).unwrap().with_defaults(&req.defaults))
+            .unzip();
+
+        assert_eq!(requests[0].url, "https://example.com/a");
+        assert_eq!(requests[0].domain.as_deref(), Some("go.example.com"));
+        assert_eq!(requests[0].expires_in_seconds, Some(3600));
+        assert_eq!(requests[1].domain.as_deref(), Some("links.example.com"));
+        assert_eq!(requests[1].expires_in_seconds, Some(3600));
+        assert_eq!(requests[2].domain.as_deref(), Some("go.example.com"));
+        assert_eq!(requests[2].expires_in_seconds, Some(60));
+        assert_eq!(requests[2].expiry_grace_seconds, Some(30));
+        assert!(requests.iter().all(|req| validate_new_link(req).is_ok()));
+        assert_eq!(tags[0], vec!["launch"]);
+        assert_eq!(tags[1], vec!["launch"]);
+        assert!(tags[2].is_empty());
+    }
+
+    #[test]
+    fn test_batch_shorten_rejects_unknown_settings() {
+        let result = serde_json::from_str::<BatchShortenRequest>(
+            r#"{"defaults": {"label": "launch"}, "urls": ["https://example.com"]}"#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_batch_shorten_item_errors_name_the_field() {
+        let error = BatchShortenItem::parse(serde_json::json!({
+            "url": "https://example.com",
+            "label": "launch"
+        }))
+        .err()
+        .unwrap();
+        assert!(error.contains("unknown field `label`"), "{}", error);
+
+        let error = BatchShortenItem::parse(serde_json::json!({"domain": "go.example.com"}))
+            .err()
+            .unwrap();
+        assert!(error.contains("missing field `url`"), "{}", error);
+
+        assert!(BatchShortenItem::parse(serde_json::json!(42)).is_err());
+    }
+
+    #[test]
+    fn test_validate_new_link() {
+        let req = |url: &str, expires: Option<i64>, grace: Option<i64>| ShortenRequest {
+            url: url.to_string(),
+            domain: None,
+            expires_in_seconds: expires,
+            expiry_grace_seconds: grace,
+        };
+
+        assert!(validate_new_link(&req("https://example.com", None, None)).is_ok());
+        assert!(validate_new_link(&req("  ", None, None)).is_err());
+        assert!(validate_new_link(&req("http://example.com", None, None)).is_err());
+        assert!(validate_new_link(&req("https://example.com", Some(0), None)).is_err());
+        assert!(validate_new_link(&req("https://example.com", None, Some(60))).is_err());
+        assert!(validate_new_link(&req("https://example.com", Some(60), Some(-1))).is_err());
+    }
+
+    #[test]
+    fn test_update_url_request_distinguishes_null_from_omitted() {
+        let req: UpdateUrlRequest =
+            serde_json::from_str(r#"{"expires_at": null, "expiry_grace_seconds": 60}"#).unwrap();
+
+        assert_eq!(req.url, None);
+        assert_eq!(req.expires_at, Some(None));
+        assert_eq!(req.expiry_grace_seconds, Some(Some(60)));
+
+        let req: UpdateUrlRequest = serde_json::from_str("{}").unwrap();
+        assert_eq!(req.expires_at, None);
+        assert_eq!(req.expiry_grace_seconds, None);
+    }
+
+    #[test]
+    fn test_validate_url_update() {
+        let now = chrono::Utc::now();
+        let parse = |body: &str| serde_json::from_str::<UpdateUrlRequest>(body).unwrap();
+
+        let update = validate_url_update(
+            parse(r#"{"url": " https://example.com/new ", "expires_at": null}"#),
+            now,
+        )
+        .unwrap();
+        assert_eq!(update.original_url.as_deref(), Some("https://example.com/new"));
+        assert_eq!(update.expires_at, Some(None));
+        assert_eq!(update.expiry_grace_seconds, None);
+
+        assert!(validate_url_update(parse("{}"), now).is_err());
+        assert!(validate_url_update(parse(r#"{"url": "http://example.com"}"#), now).is_err());
+        assert!(validate_url_update(parse(r#"{"expiry_grace_seconds": -1}"#), now).is_err());
+
+        let past = serde_json::json!({ "expires_at": now - chrono::Duration::seconds(1) });
+        assert!(validate_url_update(serde_json::from_value(past).unwrap(), now).is_err());
+
+        let future = serde_json::json!({ "expires_at": now + chrono::Duration::days(1) });
+        assert!(validate_url_update(serde_json::from_value(future).unwrap(), now).is_ok());
+    }
+
     #[tokio::test]
     async fn test_domain_validation() {
         // Test domain validation logic
@@ -0,0 +1,319 @@
+use crate::config::Settings;
+use crate::database::{DatabasePool, DatabaseService};
+use log::{info, warn};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Timeout for the HEAD request made against a link's target
+const HEAD_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Coarse media categories stored on urls.media_type
+pub const MEDIA_TYPE_CATEGORIES: [&str; 6] =
+    ["video", "audio", "image", "document", "webpage", "other"];
+
+// Map a Content-Type header value to one of MEDIA_TYPE_CATEGORIES
+pub fn categorize_content_type(content_type: &str) -> &'static str {
+    // Drop parameters such as "; charset=utf-8"
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_lowercase();
+
+    if mime.starts_with("video/") {
+        "video"
+    } else if mime.starts_with("audio/") {
+        "audio"
+    } else if mime.starts_with("image/") {
+        "image"
+    } else if mime == "text/html" || mime == "application/xhtml+xml" {
+        "webpage"
+    } else if mime == "application/pdf"
+        || mime == "text/plain"
+        || mime == "text/csv"
+        || mime == "application/rtf"
+        || mime == "application/msword"
+        || mime.starts_with("application/vnd.ms-")
+        || mime.starts_with("application/vnd.openxmlformats-officedocument.")
+        || mime.starts_with("application/vnd.oasis.opendocument.")
+    {
+        "document"
+    } else {
+        "other"
+    }
+}
+
+// Whether an address is reachable on the public internet. Link targets are user supplied, so the
+// probe refuses loopback, private, link-local and other special-purpose ranges to keep it from being
+// pointed at internal hosts.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_ipv4(mapped),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8 "this network"
+        || a == 0
+        // 100.64.0.0/10 carrier-grade NAT
+        || (a == 100 && (64..128).contains(&b))
+        // 198.18.0.0/15 benchmarking
+        || (a == 198 && (b == 18 || b == 19))
+        // 240.0.0.0/4 reserved
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // fc00::/7 unique local
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10 link-local
+        || (first & 0xffc0) == 0xfe80
+        // 2001:db8::/32 documentation
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+// Resolver for the HEAD probe that drops non-public addresses, so a hostname cannot be used to
+// reach internal hosts
+struct PublicAddressResolver;
+
+impl Resolve for PublicAddressResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} does not resolve to a public address", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// Client for the HEAD probe: no redirects, no proxies, and only public addresses
+fn probe_client() -> anyhow::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .user_agent("thalora-backend")
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy()
+        .dns_resolver(Arc::new(PublicAddressResolver))
+        .build()
+        .map_err(|e| anyhow::anyhow!("Failed to create media type HTTP client: {}", e))
+}
+
+// Whether a target may be probed; IP literals bypass DNS so they are checked here
+fn is_probe_allowed(target_url: &str) -> bool {
+    match url::Url::parse(target_url) {
+        Ok(url) => match url.host() {
+            Some(url::Host::Ipv4(ip)) => is_public_ip(IpAddr::V4(ip)),
+            Some(url::Host::Ipv6(ip)) => is_public_ip(IpAddr::V6(ip)),
+            Some(url::Host::Domain(_)) => true,
+            None => false,
+        },
+        Err(_) => false,
+    }
+}
+
+// Opt-in enrichment that records the media category of a link's target
+pub struct MediaTypeService {
+    enabled: bool,
+    cache_ttl: Duration,
+    client: reqwest::Client,
+    // Target URL -> (category or None if the lookup failed, time the entry was cached)
+    cache: Mutex<HashMap<String, (Option<&'static str>, Instant)>>,
+}
+
+impl MediaTypeService {
+    pub fn from_env(settings: &Settings) -> anyhow::Result<Self> {
+        let enabled = settings.flag("MEDIA_TYPE_ENRICHMENT");
+
+        let cache_ttl_seconds = settings
+            .get("MEDIA_TYPE_CACHE_TTL_SECONDS")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3600);
+
+        info!(
+            "Media type enrichment enabled: {} (cache TTL: {}s)",
+            enabled, cache_ttl_seconds
+        );
+
+        Ok(MediaTypeService {
+            enabled,
+            cache_ttl: Duration::from_secs(cache_ttl_seconds),
+            client: probe_client()?,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn cached_category(&self, target_url: &str) -> Option<Option<&'static str>> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .get(target_url)
+            .filter(|(_, cached_at)| cached_at.elapsed() < self.cache_ttl)
+            .map(|(category, _)| *category)
+    }
+
+    fn store_category(&self, target_url: &str, category: Option<&'static str>) {
+        let mut cache = self.cache.lock().unwrap();
+        // Drop stale entries so the cache does not grow without bound
+        let ttl = self.cache_ttl;
+        cache.retain(|_, (_, cached_at)| cached_at.elapsed() < ttl);
+        cache.insert(target_url.to_string(), (category, Instant::now()));
+    }
+
+    // Determine the target's category with a cached HEAD request; None on any failure
+    async fn lookup_category(&self, target_url: &str) -> Option<&'static str> {
+        if let Some(category) = self.cached_category(target_url) {
+            return category;
+        }
+
+        if !is_probe_allowed(target_url) {
+            warn!("Skipping HEAD request for non-public target {}", target_url);
+            self.store_category(target_url, None);
+            return None;
+        }
+
+        let category = match self
+            .client
+            .head(target_url)
+            .timeout(HEAD_REQUEST_TIMEOUT)
+            .send()
+            .await
+        {
+            Ok(response) if response.status().is_success() => response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(categorize_content_type),
+            Ok(response) => {
+                warn!(
+                    "HEAD request for {} returned status {}",
+                    target_url,
+                    response.status()
+                );
+                None
+            }
+            Err(e) => {
+                warn!("HEAD request for {} failed: {}", target_url, e);
+                None
+            }
+        };
+
+        // Failures are cached too, so an unreachable target is not probed on every redirect
+        self.store_category(target_url, category);
+        category
+    }
+
+    // Look up and persist the media type for a link; errors leave media_type NULL
+    pub async fn enrich(&self, db_pool: &DatabasePool, url_id: i64, target_url: &str) {
+        let Some(category) = self.lookup_category(target_url).await else {
+            return;
+        };
+
+        match DatabaseService::update_url_media_type(db_pool, url_id, category).await {
+            Ok(_) => info!("Recorded media type '{}' for URL ID {}", category, url_id),
+            Err(e) => warn!("Failed to record media type for URL ID {}: {}", url_id, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_categorize_content_type() {
+        assert_eq!(categorize_content_type("video/mp4"), "video");
+        assert_eq!(categorize_content_type("audio/mpeg"), "audio");
+        assert_eq!(categorize_content_type("image/png"), "image");
+        assert_eq!(
+            categorize_content_type("text/html; charset=utf-8"),
+            "webpage"
+        );
+        assert_eq!(categorize_content_type("application/pdf"), "document");
+        assert_eq!(
+            categorize_content_type(
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            ),
+            "document"
+        );
+        assert_eq!(categorize_content_type("application/octet-stream"), "other");
+        assert_eq!(categorize_content_type(""), "other");
+    }
+
+    #[test]
+    fn test_categorize_content_type_is_case_insensitive() {
+        assert_eq!(categorize_content_type("Video/MP4"), "video");
+        assert_eq!(categorize_content_type("TEXT/HTML"), "webpage");
+    }
+
+    #[test]
+    fn test_non_public_addresses_are_refused() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{} should be refused", ip);
+        }
+        for ip in ["93.184.216.34", "1.1.1.1", "2606:4700:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{} should be allowed", ip);
+        }
+    }
+
+    #[test]
+    fn test_probe_refuses_ip_literal_targets_in_private_ranges() {
+        assert!(!is_probe_allowed("https://127.0.0.1/admin"));
+        assert!(!is_probe_allowed("https://[::1]/"));
+        assert!(!is_probe_allowed("https://169.254.169.254/latest/meta-data"));
+        assert!(is_probe_allowed("https://93.184.216.34/"));
+        assert!(is_probe_allowed("https://example.com/video.mp4"));
+    }
+
+    #[test]
+    fn test_categories_are_known() {
+        for content_type in [
+            "video/webm",
+            "audio/ogg",
+            "image/gif",
+            "text/csv",
+            "font/woff2",
+        ] {
+            assert!(MEDIA_TYPE_CATEGORIES.contains(&categorize_content_type(content_type)));
+        }
+    }
+}
@@ -0,0 +1,634 @@
+use crate::config::Settings;
+use crate::database::{DatabasePool, DatabaseService};
+use actix_session::Session;
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+
+// Length of every rate-limit window
+const WINDOW_SECONDS: i64 = 60;
+
+// Tracked windows above which expired ones are pruned on the next request
+const PRUNE_THRESHOLD: usize = 10_000;
+
+// Endpoints that share a request budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitBucket {
+    Shorten,
+    Login,
+    StatusBatch,
+}
+
+impl RateLimitBucket {
+    pub const ALL: [RateLimitBucket; 3] = [
+        RateLimitBucket::Shorten,
+        RateLimitBucket::Login,
+        RateLimitBucket::StatusBatch,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitBucket::Shorten => "shorten",
+            RateLimitBucket::Login => "login",
+            RateLimitBucket::StatusBatch => "status-batch",
+        }
+    }
+}
+
+// Named per-user API throughput tier, stored on users.rate_limit_tier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitTier {
+    Free,
+    Pro,
+    Enterprise,
+}
+
+impl RateLimitTier {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "free" => Some(RateLimitTier::Free),
+            "pro" => Some(RateLimitTier::Pro),
+            "enterprise" => Some(RateLimitTier::Enterprise),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitTier::Free => "free",
+            RateLimitTier::Pro => "pro",
+            RateLimitTier::Enterprise => "enterprise",
+        }
+    }
+}
+
+// Per-minute shorten limit for signed-in users of each tier
+#[derive(Debug, Clone, Copy)]
+pub struct TierLimits {
+    pub free: u32,
+    pub pro: u32,
+    pub enterprise: u32,
+}
+
+impl TierLimits {
+    fn limit_for(&self, tier: RateLimitTier) -> u32 {
+        match tier {
+            RateLimitTier::Free => self.free,
+            RateLimitTier::Pro => self.pro,
+            RateLimitTier::Enterprise => self.enterprise,
+        }
+    }
+}
+
+// A caller's standing in one bucket
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BucketStatus {
+    pub bucket: &'static str,
+    // Tier whose limit applies, when the bucket is tiered and the caller is signed in
+    pub tier: Option<&'static str>,
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset_at: DateTime<Utc>,
+}
+
+struct Window {
+    started_at: DateTime<Utc>,
+    count: u32,
+}
+
+// In-memory fixed-window limiter keyed by bucket and caller
+pub struct RateLimiter {
+    shorten_limit: u32,
+    login_limit: u32,
+    status_batch_limit: u32,
+    tier_limits: TierLimits,
+    // Identify anonymous callers by the client address a reverse proxy reports
+    trust_proxy_headers: bool,
+    windows: Mutex<HashMap<(RateLimitBucket, String), Window>>,
+}
+
+fn parse_limit(settings: &Settings, name: &str, default: u32) -> anyhow::Result<u32> {
+    match settings.get(name) {
+        Some(value) => value
+            .trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|limit| *limit > 0)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid {} '{}'. Expected a positive number of requests per minute",
+                    name,
+                    value
+                )
+            }),
+        None => Ok(default),
+    }
+}
+
+impl RateLimiter {
+    pub fn new(
+        shorten_limit: u32,
+        login_limit: u32,
+        status_batch_limit: u32,
+        tier_limits: TierLimits,
+        trust_proxy_headers: bool,
+    ) -> Self {
+        RateLimiter {
+            shorten_limit,
+            login_limit,
+            status_batch_limit,
+            tier_limits,
+            trust_proxy_headers,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Read RATE_LIMIT_SHORTEN_PER_MINUTE (default 30), RATE_LIMIT_LOGIN_PER_MINUTE (default 10) and
+    // RATE_LIMIT_STATUS_BATCH_PER_MINUTE (default 10), plus the signed-in shorten limits per tier:
+    // RATE_LIMIT_TIER_FREE_PER_MINUTE (default: the shorten limit), RATE_LIMIT_TIER_PRO_PER_MINUTE
+    // (default 120) and RATE_LIMIT_TIER_ENTERPRISE_PER_MINUTE (default 600). TRUST_PROXY_HEADERS
+    // (default false) keys anonymous callers by the forwarded client address.
+    pub fn from_env(settings: &Settings) -> anyhow::Result<Self> {
+        let shorten_limit = parse_limit(settings, "RATE_LIMIT_SHORTEN_PER_MINUTE", 30)?;
+        let login_limit = parse_limit(settings, "RATE_LIMIT_LOGIN_PER_MINUTE", 10)?;
+        let status_batch_limit = parse_limit(settings, "RATE_LIMIT_STATUS_BATCH_PER_MINUTE", 10)?;
+        let tier_limits = TierLimits {
+            free: parse_limit(settings, "RATE_LIMIT_TIER_FREE_PER_MINUTE", shorten_limit)?,
+            pro: parse_limit(settings, "RATE_LIMIT_TIER_PRO_PER_MINUTE", 120)?,
+            enterprise: parse_limit(settings, "RATE_LIMIT_TIER_ENTERPRISE_PER_MINUTE", 600)?,
+        };
+        let trust_proxy_headers = settings.flag("TRUST_PROXY_HEADERS");
+
+        info!(
+            "Rate limits per minute: shorten {}, login {}, status batch {}; tiers: free {}, pro {}, enterprise {}",
+            shorten_limit,
+            login_limit,
+            status_batch_limit,
+            tier_limits.free,
+            tier_limits.pro,
+            tier_limits.enterprise
+        );
+        info!("Trust proxy headers for client IPs: {}", trust_proxy_headers);
+
+        Ok(Self::new(
+            shorten_limit,
+            login_limit,
+            status_batch_limit,
+            tier_limits,
+            trust_proxy_headers,
+        ))
+    }
+
+    // The client's IP address. Behind a trusted reverse proxy (TRUST_PROXY_HEADERS=true) this is the
+    // address reported in Forwarded or X-Forwarded-For, falling back to the peer address when the
+    // header is missing or not an IP. Otherwise it is always the peer address, since any client can
+    // set those headers.
+    pub fn client_ip(&self, http_req: &HttpRequest) -> Option<String> {
+        let peer_ip = || http_req.peer_addr().map(|addr| addr.ip().to_string());
+        if !self.trust_proxy_headers {
+            return peer_ip();
+        }
+
+        let connection_info = http_req.connection_info();
+        let forwarded = connection_info.realip_remote_addr().and_then(|addr| {
+            let addr = addr.trim();
+            addr.parse::<IpAddr>()
+                .or_else(|_| addr.parse::<SocketAddr>().map(|addr| addr.ip()))
+                .ok()
+        });
+        forwarded.map(|ip| ip.to_string()).or_else(peer_ip)
+    }
+
+    // Tiers apply to the shorten bucket; the other buckets guard against abuse and stay flat
+    fn applied_tier(bucket: RateLimitBucket, tier: Option<RateLimitTier>) -> Option<RateLimitTier> {
+        tier.filter(|_| bucket == RateLimitBucket::Shorten)
+    }
+
+    pub fn limit_for(&self, bucket: RateLimitBucket, tier: Option<RateLimitTier>) -> u32 {
+        if let Some(tier) = Self::applied_tier(bucket, tier) {
+            return self.tier_limits.limit_for(tier);
+        }
+
+        match bucket {
+            RateLimitBucket::Shorten => self.shorten_limit,
+            RateLimitBucket::Login => self.login_limit,
+            RateLimitBucket::StatusBatch => self.status_batch_limit,
+        }
+    }
+
+    fn status_of(
+        &self,
+        bucket: RateLimitBucket,
+        tier: Option<RateLimitTier>,
+        window: Option<&Window>,
+        now: DateTime<Utc>,
+    ) -> BucketStatus {
+        let limit = self.limit_for(bucket, tier);
+        let tier = Self::applied_tier(bucket, tier).map(|tier| tier.as_str());
+        let window_length = Duration::seconds(WINDOW_SECONDS);
+
+        match window.filter(|window| now < window.started_at + window_length) {
+            Some(window) => BucketStatus {
+                bucket: bucket.as_str(),
+                tier,
+                limit,
+                remaining: limit.saturating_sub(window.count),
+                reset_at: window.started_at + window_length,
+            },
+            // No requests in the current window: the full budget is available
+            None => BucketStatus {
+                bucket: bucket.as_str(),
+                tier,
+                limit,
+                remaining: limit,
+                reset_at: now + window_length,
+            },
+        }
+    }
+
+    // Current standing without consuming a request
+    pub fn status(
+        &self,
+        bucket: RateLimitBucket,
+        key: &str,
+        tier: Option<RateLimitTier>,
+        now: DateTime<Utc>,
+    ) -> BucketStatus {
+        let windows = self.windows.lock().unwrap();
+        self.status_of(bucket, tier, windows.get(&(bucket, key.to_string())), now)
+    }
+
+    // Record a request costing `cost` units (one per link in a batch, otherwise one); Err carries
+    // the status to report when less than `cost` remains, and nothing is consumed then
+    pub fn check(
+        &self,
+        bucket: RateLimitBucket,
+        key: &str,
+        tier: Option<RateLimitTier>,
+        cost: u32,
+        now: DateTime<Utc>,
+    ) -> Result<BucketStatus, BucketStatus> {
+        let mut windows = self.windows.lock().unwrap();
+        let window_length = Duration::seconds(WINDOW_SECONDS);
+
+        if windows.len() > PRUNE_THRESHOLD {
+            windows.retain(|_, window| now < window.started_at + window_length);
+        }
+
+        let window = windows
+            .entry((bucket, key.to_string()))
+            .or_insert_with(|| Window {
+                started_at: now,
+                count: 0,
+            });
+        if now >= window.started_at + window_length {
+            window.started_at = now;
+            window.count = 0;
+        }
+
+        if window.count.saturating_add(cost) > self.limit_for(bucket, tier) {
+            return Err(self.status_of(bucket, tier, Some(window), now));
+        }
+
+        window.count += cost;
+        Ok(self.status_of(bucket, tier, Some(window), now))
+    }
+}
+
+// Key a caller by user when signed in, otherwise by client IP (see RateLimiter::client_ip). Login
+// is always keyed by IP since the caller is not signed in yet.
+pub fn caller_key(
+    rate_limiter: &RateLimiter,
+    bucket: RateLimitBucket,
+    session: &Session,
+    http_req: &HttpRequest,
+) -> String {
+    if bucket != RateLimitBucket::Login {
+        if let Ok(Some(user_id)) = session.get::<i64>("user_id") {
+            return format!("user:{}", user_id);
+        }
+    }
+
+    match rate_limiter.client_ip(http_req) {
+        Some(ip) => format!("ip:{}", ip),
+        None => "ip:unknown".to_string(),
+    }
+}
+
+// The signed-in caller's tier; None for anonymous callers. Unknown tier names fall back to free.
+pub async fn caller_tier(session: &Session, db_pool: &DatabasePool) -> Option<RateLimitTier> {
+    let user_id = session.get::<i64>("user_id").ok().flatten()?;
+
+    match DatabaseService::get_user_by_id(db_pool, user_id).await {
+        Ok(Some(user)) => Some(
+            RateLimitTier::parse(&user.rate_limit_tier).unwrap_or_else(|| {
+                warn!(
+                    "User {} has unknown rate limit tier '{}', using free",
+                    user_id, user.rate_limit_tier
+                );
+                RateLimitTier::Free
+            }),
+        ),
+        Ok(None) => None,
+        Err(e) => {
+            warn!(
+                "Failed to look up rate limit tier for user {}: {}",
+                user_id, e
+            );
+            Some(RateLimitTier::Free)
+        }
+    }
+}
+
+// Consume a request from the caller's budget, returning the 429 response to send when it is exhausted
+pub fn enforce(
+    rate_limiter: &RateLimiter,
+    bucket: RateLimitBucket,
+    tier: Option<RateLimitTier>,
+    session: &Session,
+    http_req: &HttpRequest,
+) -> Option<HttpResponse> {
+    enforce_n(rate_limiter, bucket, tier, 1, session, http_req)
+}
+
+// Consume `cost` units of the caller's budget, returning the 429 response to send when less remains
+pub fn enforce_n(
+    rate_limiter: &RateLimiter,
+    bucket: RateLimitBucket,
+    tier: Option<RateLimitTier>,
+    cost: u32,
+    session: &Session,
+    http_req: &HttpRequest,
+) -> Option<HttpResponse> {
+    let key = caller_key(rate_limiter, bucket, session, http_req);
+    let now = Utc::now();
+
+    rate_limiter
+        .check(bucket, &key, tier, cost, now)
+        .err()
+        .map(|status| {
+            warn!("Rate limit exceeded for {} on {}", key, bucket.as_str());
+            let retry_after = (status.reset_at - now).num_seconds().max(1);
+            HttpResponse::TooManyRequests()
+                .append_header(("Retry-After", retry_after.to_string()))
+                .append_header(("X-RateLimit-Limit", status.limit.to_string()))
+                .json(serde_json::json!({
+                    "error": "Rate limit exceeded. Please try again later.",
+                    "bucket": status.bucket,
+                    "tier": status.tier,
+                    "limit": status.limit,
+                    "remaining": status.remaining,
+                    "reset_at": status.reset_at,
+                }))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap()
+    }
+
+    fn limiter_with(shorten_limit: u32, login_limit: u32, status_batch_limit: u32) -> RateLimiter {
+        RateLimiter::new(
+            shorten_limit,
+            login_limit,
+            status_batch_limit,
+            TierLimits {
+                free: 5,
+                pro: 50,
+                enterprise: 500,
+            },
+            false,
+        )
+    }
+
+    fn proxied_request(forwarded_for: &str) -> HttpRequest {
+        actix_web::test::TestRequest::default()
+            .peer_addr("10.0.0.2:443".parse().unwrap())
+            .insert_header(("X-Forwarded-For", forwarded_for))
+            .to_http_request()
+    }
+
+    #[test]
+    fn test_client_ip_ignores_forwarding_headers_by_default() {
+        let limiter = limiter_with(3, 2, 1);
+
+        assert_eq!(
+            limiter.client_ip(&proxied_request("203.0.113.7")).as_deref(),
+            Some("10.0.0.2")
+        );
+    }
+
+    #[test]
+    fn test_client_ip_uses_forwarded_address_behind_trusted_proxy() {
+        let limiter = RateLimiter {
+            trust_proxy_headers: true,
+            ..limiter_with(3, 2, 1)
+        };
+
+        assert_eq!(
+            limiter
+                .client_ip(&proxied_request("203.0.113.7, 10.0.0.1"))
+                .as_deref(),
+            Some("203.0.113.7")
+        );
+        assert_eq!(
+            limiter.client_ip(&proxied_request("203.0.113.7:51234")).as_deref(),
+            Some("203.0.113.7")
+        );
+        // Junk in the header falls back to the peer address
+        assert_eq!(
+            limiter.client_ip(&proxied_request("unknown")).as_deref(),
+            Some("10.0.0.2")
+        );
+    }
+
+    #[test]
+    fn test_status_before_any_request_is_full() {
+        let limiter = limiter_with(3, 2, 1);
+
+        let status = limiter.status(RateLimitBucket::Shorten, "user:1", None, now());
+
+        assert_eq!(status.limit, 3);
+        assert_eq!(status.remaining, 3);
+        assert_eq!(status.reset_at, now() + Duration::seconds(WINDOW_SECONDS));
+    }
+
+    #[test]
+    fn test_check_consumes_until_limit() {
+        let limiter = limiter_with(2, 2, 1);
+
+        assert_eq!(
+            limiter
+                .check(RateLimitBucket::Shorten, "user:1", None, 1, now())
+                .unwrap()
+                .remaining,
+            1
+        );
+        assert_eq!(
+            limiter
+                .check(RateLimitBucket::Shorten, "user:1", None, 1, now())
+                .unwrap()
+                .remaining,
+            0
+        );
+
+        let rejected = limiter
+            .check(RateLimitBucket::Shorten, "user:1", None, 1, now())
+            .unwrap_err();
+        assert_eq!(rejected.remaining, 0);
+        assert_eq!(rejected.reset_at, now() + Duration::seconds(WINDOW_SECONDS));
+    }
+
+    #[test]
+    fn test_check_charges_cost_or_nothing() {
+        let limiter = limiter_with(5, 2, 1);
+
+        assert_eq!(
+            limiter
+                .check(RateLimitBucket::Shorten, "user:1", None, 3, now())
+                .unwrap()
+                .remaining,
+            2
+        );
+
+        // Too little budget left for the batch: rejected without consuming any of it
+        let rejected = limiter
+            .check(RateLimitBucket::Shorten, "user:1", None, 3, now())
+            .unwrap_err();
+        assert_eq!(rejected.remaining, 2);
+        assert_eq!(
+            limiter
+                .check(RateLimitBucket::Shorten, "user:1", None, 2, now())
+                .unwrap()
+                .remaining,
+            0
+        );
+    }
+
+    #[test]
+    fn test_status_does_not_consume() {
+        let limiter = limiter_with(2, 2, 1);
+        limiter
+            .check(RateLimitBucket::Login, "ip:127.0.0.1", None, 1, now())
+            .unwrap();
+
+        for _ in 0..3 {
+            assert_eq!(
+                limiter
+                    .status(RateLimitBucket::Login, "ip:127.0.0.1", None, now())
+                    .remaining,
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn test_buckets_and_callers_are_independent() {
+        let limiter = limiter_with(1, 1, 1);
+        limiter
+            .check(RateLimitBucket::Shorten, "user:1", None, 1, now())
+            .unwrap();
+
+        assert!(limiter
+            .check(RateLimitBucket::Shorten, "user:2", None, 1, now())
+            .is_ok());
+        assert!(limiter
+            .check(RateLimitBucket::Login, "user:1", None, 1, now())
+            .is_ok());
+        assert!(limiter
+            .check(RateLimitBucket::Shorten, "user:1", None, 1, now())
+            .is_err());
+    }
+
+    #[test]
+    fn test_window_resets() {
+        let limiter = limiter_with(1, 1, 1);
+        limiter
+            .check(RateLimitBucket::Shorten, "user:1", None, 1, now())
+            .unwrap();
+        let later = now() + Duration::seconds(WINDOW_SECONDS);
+
+        assert_eq!(
+            limiter
+                .status(RateLimitBucket::Shorten, "user:1", None, later)
+                .remaining,
+            1
+        );
+        assert!(limiter
+            .check(RateLimitBucket::Shorten, "user:1", None, 1, later)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_tier_sets_shorten_limit() {
+        let limiter = limiter_with(3, 2, 1);
+
+        let anonymous = limiter.status(RateLimitBucket::Shorten, "ip:127.0.0.1", None, now());
+        assert_eq!(anonymous.limit, 3);
+        assert_eq!(anonymous.tier, None);
+
+        let pro = limiter.status(
+            RateLimitBucket::Shorten,
+            "user:1",
+            Some(RateLimitTier::Pro),
+            now(),
+        );
+        assert_eq!(pro.limit, 50);
+        assert_eq!(pro.tier, Some("pro"));
+
+        // Login and status batch limits do not depend on the tier
+        let login = limiter.status(
+            RateLimitBucket::Login,
+            "ip:127.0.0.1",
+            Some(RateLimitTier::Enterprise),
+            now(),
+        );
+        assert_eq!(login.limit, 2);
+        assert_eq!(login.tier, None);
+    }
+
+    #[test]
+    fn test_tier_limit_is_enforced() {
+        let limiter = limiter_with(1, 1, 1);
+
+        for _ in 0..5 {
+            assert!(limiter
+                .check(
+                    RateLimitBucket::Shorten,
+                    "user:1",
+                    Some(RateLimitTier::Free),
+                    1,
+                    now()
+                )
+                .is_ok());
+        }
+        assert!(limiter
+            .check(
+                RateLimitBucket::Shorten,
+                "user:1",
+                Some(RateLimitTier::Free),
+                1,
+                now()
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_tier() {
+        assert_eq!(RateLimitTier::parse("Pro"), Some(RateLimitTier::Pro));
+        assert_eq!(
+            RateLimitTier::parse(" enterprise "),
+            Some(RateLimitTier::Enterprise)
+        );
+        assert_eq!(RateLimitTier::parse("platinum"), None);
+    }
+}
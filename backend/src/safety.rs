@@ -0,0 +1,326 @@
+use crate::config::Settings;
+use crate::export::escape_html;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Keep the reputation lookup short; it runs on the redirect path
+const REPUTATION_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+// How long a failed lookup is remembered before the checker is asked again
+const FAILED_LOOKUP_CACHE_TTL: Duration = Duration::from_secs(60);
+
+// What visitors get when a link's target is flagged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlaggedTargetResponse {
+    // Warning page that names the target and lets the visitor continue at their own risk
+    Interstitial,
+    // Plain 403 without revealing the target
+    Forbidden,
+}
+
+impl FlaggedTargetResponse {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "interstitial" => Some(FlaggedTargetResponse::Interstitial),
+            "forbidden" => Some(FlaggedTargetResponse::Forbidden),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ReputationRequest<'a> {
+    url: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ReputationVerdict {
+    flagged: bool,
+}
+
+type VerdictFuture<'a> = Pin<Box<dyn Future<Output = Option<bool>> + Send + 'a>>;
+
+// Source of reputation verdicts: whether a target is flagged, or None when the lookup failed
+trait ReputationLookup: Send + Sync {
+    fn lookup<'a>(&'a self, target_url: &'a str) -> VerdictFuture<'a>;
+}
+
+// Asks the reputation service at REPUTATION_CHECK_URL
+struct HttpReputationLookup {
+    check_url: String,
+    client: reqwest::Client,
+}
+
+impl ReputationLookup for HttpReputationLookup {
+    fn lookup<'a>(&'a self, target_url: &'a str) -> VerdictFuture<'a> {
+        Box::pin(async move {
+            let response = self
+                .client
+                .post(&self.check_url)
+                .timeout(REPUTATION_REQUEST_TIMEOUT)
+                .json(&ReputationRequest { url: target_url })
+                .send()
+                .await;
+
+            match response {
+                Ok(response) if response.status().is_success() => {
+                    match response.json::<ReputationVerdict>().await {
+                        Ok(verdict) => Some(verdict.flagged),
+                        Err(e) => {
+                            warn!("Invalid reputation verdict for {}: {}", target_url, e);
+                            None
+                        }
+                    }
+                }
+                Ok(response) => {
+                    warn!(
+                        "Reputation check for {} returned status {}",
+                        target_url,
+                        response.status()
+                    );
+                    None
+                }
+                Err(e) => {
+                    warn!("Reputation check for {} failed: {}", target_url, e);
+                    None
+                }
+            }
+        })
+    }
+}
+
+// Optional redirect-time re-check of link targets against a reputation service
+pub struct SafetyCheckService {
+    enabled: bool,
+    // None when no REPUTATION_CHECK_URL is configured
+    lookup: Option<Box<dyn ReputationLookup>>,
+    flagged_response: FlaggedTargetResponse,
+    cache_ttl: Duration,
+    // Target URL -> (flagged, or None if the lookup failed, time the entry was cached)
+    cache: Mutex<HashMap<String, (Option<bool>, Instant)>>,
+}
+
+impl SafetyCheckService {
+    // Read REDIRECT_TIME_SAFETY_CHECK (default false), REPUTATION_CHECK_URL (required when enabled),
+    // REDIRECT_FLAGGED_RESPONSE (default interstitial) and REPUTATION_CACHE_TTL_SECONDS (default 86400)
+    pub fn from_env(settings: &Settings, client: reqwest::Client) -> anyhow::Result<Self> {
+        let enabled = settings.flag("REDIRECT_TIME_SAFETY_CHECK");
+
+        let check_url = settings
+            .get("REPUTATION_CHECK_URL")
+            .filter(|url| !url.trim().is_empty());
+        if enabled && check_url.is_none() {
+            return Err(anyhow::anyhow!(
+                "REDIRECT_TIME_SAFETY_CHECK=true requires REPUTATION_CHECK_URL to be set"
+            ));
+        }
+
+        let flagged_response = match settings.get("REDIRECT_FLAGGED_RESPONSE") {
+            Some(value) => FlaggedTargetResponse::parse(&value).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid REDIRECT_FLAGGED_RESPONSE '{}'. Expected 'interstitial' or 'forbidden'",
+                    value
+                )
+            })?,
+            None => FlaggedTargetResponse::Interstitial,
+        };
+
+        let cache_ttl_seconds = settings
+            .get("REPUTATION_CACHE_TTL_SECONDS")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(86400);
+
+        info!(
+            "Redirect-time safety check enabled: {} (cache TTL: {}s)",
+            enabled, cache_ttl_seconds
+        );
+
+        Ok(SafetyCheckService {
+            enabled,
+            lookup: check_url.map(|check_url| {
+                Box::new(HttpReputationLookup { check_url, client }) as Box<dyn ReputationLookup>
+            }),
+            flagged_response,
+            cache_ttl: Duration::from_secs(cache_ttl_seconds),
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn flagged_response(&self) -> FlaggedTargetResponse {
+        self.flagged_response
+    }
+
+    fn cached_verdict(&self, target_url: &str) -> Option<Option<bool>> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .get(target_url)
+            .filter(|(verdict, cached_at)| {
+                let ttl = if verdict.is_some() {
+                    self.cache_ttl
+                } else {
+                    FAILED_LOOKUP_CACHE_TTL.min(self.cache_ttl)
+                };
+                cached_at.elapsed() < ttl
+            })
+            .map(|(verdict, _)| *verdict)
+    }
+
+    fn store_verdict(&self, target_url: &str, verdict: Option<bool>) {
+        let mut cache = self.cache.lock().unwrap();
+        // Drop stale entries so the cache does not grow without bound
+        let ttl = self.cache_ttl;
+        cache.retain(|_, (_, cached_at)| cached_at.elapsed() < ttl);
+        cache.insert(target_url.to_string(), (verdict, Instant::now()));
+    }
+
+    // Whether the target is currently flagged. Lookups that fail are treated as not flagged so an
+    // unavailable checker never blocks redirects.
+    pub async fn is_flagged(&self, target_url: &str) -> bool {
+        let Some(lookup) = self.lookup.as_deref().filter(|_| self.enabled) else {
+            return false;
+        };
+
+        let verdict = match self.cached_verdict(target_url) {
+            Some(verdict) => verdict,
+            None => {
+                let verdict = lookup.lookup(target_url).await;
+                self.store_verdict(target_url, verdict);
+                verdict
+            }
+        };
+
+        verdict.unwrap_or(false)
+    }
+}
+
+// Warning page served instead of redirecting to a flagged target
+pub fn render_interstitial(target_url: &str) -> String {
+    let target_url = escape_html(target_url);
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta charset="utf-8">
+    <meta name="robots" content="noindex">
+    <meta name="referrer" content="no-referrer">
+    <title>Warning: flagged link - Thalora</title>
+  </head>
+  <body>
+    <h1>This link may be unsafe</h1>
+    <p>The destination of this short link has been flagged as potentially harmful since it was created.</p>
+    <p>Destination: <code>{target_url}</code></p>
+    <p><a href="{target_url}" rel="noopener noreferrer nofollow">Continue anyway</a></p>
+  </body>
+</html>
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Answers every lookup with a fixed verdict and counts the lookups made
+    struct StubLookup {
+        verdict: Option<bool>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl ReputationLookup for StubLookup {
+        fn lookup<'a>(&'a self, _target_url: &'a str) -> VerdictFuture<'a> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move { self.verdict })
+        }
+    }
+
+    // A service whose uncached lookups return `verdict`, with the counter of lookups made
+    fn service(enabled: bool, verdict: Option<bool>) -> (SafetyCheckService, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let service = SafetyCheckService {
+            enabled,
+            lookup: Some(Box::new(StubLookup {
+                verdict,
+                calls: calls.clone(),
+            })),
+            flagged_response: FlaggedTargetResponse::Interstitial,
+            cache_ttl: Duration::from_secs(3600),
+            cache: Mutex::new(HashMap::new()),
+        };
+        (service, calls)
+    }
+
+    #[test]
+    fn test_parse_flagged_response() {
+        assert_eq!(
+            FlaggedTargetResponse::parse("Interstitial"),
+            Some(FlaggedTargetResponse::Interstitial)
+        );
+        assert_eq!(
+            FlaggedTargetResponse::parse(" forbidden "),
+            Some(FlaggedTargetResponse::Forbidden)
+        );
+        assert_eq!(FlaggedTargetResponse::parse("block"), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_cached_verdicts_are_used() {
+        let (service, calls) = service(true, None);
+        service.store_verdict("https://bad.example.com/", Some(true));
+        service.store_verdict("https://good.example.com/", Some(false));
+
+        assert!(service.is_flagged("https://bad.example.com/").await);
+        assert!(!service.is_flagged("https://good.example.com/").await);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[actix_rt::test]
+    async fn test_lookup_verdict_is_cached() {
+        let (service, calls) = service(true, Some(true));
+
+        assert!(service.is_flagged("https://bad.example.com/").await);
+        assert!(service.is_flagged("https://bad.example.com/").await);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_rt::test]
+    async fn test_failed_lookup_is_not_flagged() {
+        let (service, calls) = service(true, None);
+
+        assert!(!service.is_flagged("https://unknown.example.com/").await);
+        assert_eq!(
+            service.cached_verdict("https://unknown.example.com/"),
+            Some(None)
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_rt::test]
+    async fn test_disabled_check_never_flags() {
+        let (service, calls) = service(false, Some(true));
+        service.store_verdict("https://bad.example.com/", Some(true));
+
+        assert!(!service.is_flagged("https://bad.example.com/").await);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_interstitial_escapes_target() {
+        let page = render_interstitial("https://bad.example.com/?a=1&b=<script>");
+
+        assert!(page.contains("This link may be unsafe"));
+        assert!(page.contains("https://bad.example.com/?a=1&amp;b=&lt;script&gt;"));
+        assert!(!page.contains("<script>"));
+    }
+}
@@ -0,0 +1,96 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::HashMap;
+
+// Redirects of one link on one UTC day
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DailyCount {
+    pub date: NaiveDate,
+    pub clicks: i64,
+}
+
+// One entry per day from start to end inclusive, with zero for days without redirects
+pub fn fill_daily_counts(
+    rows: &[(NaiveDate, i64)],
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Vec<DailyCount> {
+    let clicks_by_day: HashMap<NaiveDate, i64> = rows.iter().copied().collect();
+
+    start
+        .iter_days()
+        .take_while(|date| *date <= end)
+        .map(|date| DailyCount {
+            date,
+            clicks: clicks_by_day.get(&date).copied().unwrap_or(0),
+        })
+        .collect()
+}
+
+// Render daily counts as CSV with a header row
+pub fn render_daily_counts_csv(counts: &[DailyCount]) -> String {
+    let mut csv = String::from("date,clicks\n");
+    for count in counts {
+        csv.push_str(&format!(
+            "{},{}\n",
+            count.date.format("%Y-%m-%d"),
+            count.clicks
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, day).unwrap()
+    }
+
+    #[test]
+    fn test_fill_daily_counts() {
+        let counts = fill_daily_counts(&[(date(2), 5), (date(4), 1)], date(1), date(4));
+
+        assert_eq!(
+            counts,
+            vec![
+                DailyCount {
+                    date: date(1),
+                    clicks: 0
+                },
+                DailyCount {
+                    date: date(2),
+                    clicks: 5
+                },
+                DailyCount {
+                    date: date(3),
+                    clicks: 0
+                },
+                DailyCount {
+                    date: date(4),
+                    clicks: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fill_daily_counts_ignores_rows_outside_range() {
+        let counts = fill_daily_counts(&[(date(10), 3)], date(1), date(2));
+
+        assert_eq!(counts.iter().map(|c| c.clicks).sum::<i64>(), 0);
+        assert_eq!(counts.len(), 2);
+    }
+
+    #[test]
+    fn test_daily_counts_csv() {
+        let csv = render_daily_counts_csv(&fill_daily_counts(&[(date(2), 5)], date(1), date(2)));
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines[0], "date,clicks");
+        assert_eq!(lines[1], "2025-03-01,0");
+        assert_eq!(lines[2], "2025-03-02,5");
+        assert_eq!(lines.len(), 3);
+    }
+}
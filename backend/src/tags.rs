@@ -0,0 +1,106 @@
+// Longest tag name stored (tags.name is NVARCHAR(50))
+pub const MAX_TAG_LENGTH: usize = 50;
+
+// Most tags one link can carry
+pub const MAX_TAGS_PER_LINK: usize = 10;
+
+// Check a tag name, returning it trimmed. Names are letters, digits, spaces, '-' and '_'.
+pub fn validate_tag_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Tag names cannot be empty".to_string());
+    }
+    if name.chars().count() > MAX_TAG_LENGTH {
+        return Err(format!(
+            "Tag '{}' is longer than {} characters",
+            name, MAX_TAG_LENGTH
+        ));
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_alphanumeric() || matches!(c, ' ' | '-' | '_')))
+    {
+        return Err(format!(
+            "Tag '{}' contains unsupported character '{}'",
+            name, c
+        ));
+    }
+    Ok(name.to_string())
+}
+
+// Validate a list of tags for one link, keeping the valid ones. Duplicates (ignoring case, as tag
+// names are unique per owner regardless of case) keep their first spelling; each invalid or
+// excess tag is reported as an error.
+pub fn validate_tags<'a>(names: impl IntoIterator<Item = &'a str>) -> (Vec<String>, Vec<String>) {
+    let mut tags: Vec<String> = Vec::new();
+    let mut errors = Vec::new();
+    for name in names {
+        match validate_tag_name(name) {
+            Ok(tag) => {
+                if tags
+                    .iter()
+                    .any(|existing| existing.to_lowercase() == tag.to_lowercase())
+                {
+                    continue;
+                }
+                if tags.len() == MAX_TAGS_PER_LINK {
+                    errors.push(format!(
+                        "Tag '{}' skipped: a link can have at most {} tags",
+                        tag, MAX_TAGS_PER_LINK
+                    ));
+                    continue;
+                }
+                tags.push(tag);
+            }
+            Err(error) => errors.push(error),
+        }
+    }
+    (tags, errors)
+}
+
+// Parse a semicolon-separated tag list such as "campaign;q3 launch", ignoring empty entries
+pub fn parse_tag_list(value: &str) -> (Vec<String>, Vec<String>) {
+    validate_tags(value.split(';').filter(|name| !name.trim().is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_tag_name() {
+        assert_eq!(validate_tag_name("  q3 launch ").unwrap(), "q3 launch");
+        assert_eq!(
+            validate_tag_name("social-media_2026").unwrap(),
+            "social-media_2026"
+        );
+        assert!(validate_tag_name("   ").is_err());
+        assert!(validate_tag_name("a/b").is_err());
+        assert!(validate_tag_name(&"x".repeat(MAX_TAG_LENGTH + 1)).is_err());
+        assert!(validate_tag_name(&"x".repeat(MAX_TAG_LENGTH)).is_ok());
+    }
+
+    #[test]
+    fn test_parse_tag_list() {
+        let (tags, errors) = parse_tag_list("campaign; Q3 ;;campaign;CAMPAIGN;bad!tag");
+        assert_eq!(tags, vec!["campaign", "Q3"]);
+        assert_eq!(
+            errors,
+            vec!["Tag 'bad!tag' contains unsupported character '!'"]
+        );
+
+        let (tags, errors) = parse_tag_list("");
+        assert!(tags.is_empty());
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_parse_tag_list_caps_tags_per_link() {
+        let list: Vec<String> = (0..MAX_TAGS_PER_LINK + 2)
+            .map(|i| format!("t{}", i))
+            .collect();
+        let (tags, errors) = parse_tag_list(&list.join(";"));
+        assert_eq!(tags.len(), MAX_TAGS_PER_LINK);
+        assert_eq!(errors.len(), 2);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::config::Settings;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use log::info;
+use serde::Serialize;
+use sha2::Sha256;
+use std::time::{Duration, Instant};
+
+// Webhook receivers must answer within this window
+const WEBHOOK_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub const SIGNATURE_HEADER: &str = "X-Thalora-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Thalora-Timestamp";
+pub const EVENT_HEADER: &str = "X-Thalora-Event";
+
+// Envelope posted to the webhook URL
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookEvent {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub created_at: DateTime<Utc>,
+    pub data: serde_json::Value,
+}
+
+impl WebhookEvent {
+    // Sample event used to check that a receiver accepts and verifies deliveries
+    pub fn test(now: DateTime<Utc>) -> Self {
+        WebhookEvent {
+            id: uuid::Uuid::new_v4().to_string(),
+            event_type: "webhook.test".to_string(),
+            created_at: now,
+            data: serde_json::json!({
+                "message": "This is a test event from Thalora",
+            }),
+        }
+    }
+}
+
+// Outcome of one delivery attempt
+#[derive(Debug)]
+pub struct WebhookDelivery {
+    pub status: u16,
+    pub elapsed: Duration,
+}
+
+// Hex HMAC-SHA256 of "{timestamp}.{body}"; receivers recompute it from the timestamp header and raw body
+pub fn sign_payload(secret: &[u8], timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+// Signed delivery of events to the operator's webhook receiver
+pub struct WebhookService {
+    url: Option<String>,
+    signing_secret: String,
+    client: reqwest::Client,
+}
+
+impl WebhookService {
+    // Read WEBHOOK_URL (optional) and WEBHOOK_SIGNING_SECRET (required when WEBHOOK_URL is set)
+    pub fn from_env(settings: &Settings, client: reqwest::Client) -> anyhow::Result<Self> {
+        let url = settings
+            .get("WEBHOOK_URL")
+            .filter(|url| !url.trim().is_empty());
+
+        let signing_secret = settings.get("WEBHOOK_SIGNING_SECRET").unwrap_or_default();
+        if url.is_some() && signing_secret.is_empty() {
+            return Err(anyhow::anyhow!(
+                "WEBHOOK_URL requires WEBHOOK_SIGNING_SECRET to be set"
+            ));
+        }
+
+        if let Some(url) = &url {
+            let parsed = url::Url::parse(url)
+                .map_err(|e| anyhow::anyhow!("Invalid WEBHOOK_URL '{}': {}", url, e))?;
+            if parsed.scheme() != "https" && parsed.scheme() != "http" {
+                return Err(anyhow::anyhow!(
+                    "Invalid WEBHOOK_URL '{}'. Expected an http or https URL",
+                    url
+                ));
+            }
+        }
+
+        info!("Webhook delivery configured: {}", url.is_some());
+
+        Ok(WebhookService {
+            url,
+            signing_secret,
+            client,
+        })
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.url.is_some()
+    }
+
+    // Post a signed event to the webhook URL and report the receiver's status and response time
+    pub async fn deliver(&self, event: &WebhookEvent) -> anyhow::Result<WebhookDelivery> {
+        let url = self
+            .url
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("No webhook URL configured"))?;
+
+        let body = serde_json::to_vec(event)?;
+        let timestamp = Utc::now().timestamp();
+        let signature = sign_payload(self.signing_secret.as_bytes(), timestamp, &body);
+
+        let started = Instant::now();
+        let response = self
+            .client
+            .post(url)
+            .timeout(WEBHOOK_REQUEST_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, signature)
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(EVENT_HEADER, &event.event_type)
+            .body(body)
+            .send()
+            .await?;
+
+        Ok(WebhookDelivery {
+            status: response.status().as_u16(),
+            elapsed: started.elapsed(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_covers_timestamp_and_body() {
+        let signature = sign_payload(b"secret", 1700000000, b"{\"a\":1}");
+
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_eq!(signature, sign_payload(b"secret", 1700000000, b"{\"a\":1}"));
+        assert_ne!(signature, sign_payload(b"secret", 1700000001, b"{\"a\":1}"));
+        assert_ne!(signature, sign_payload(b"secret", 1700000000, b"{\"a\":2}"));
+        assert_ne!(signature, sign_payload(b"other", 1700000000, b"{\"a\":1}"));
+    }
+
+    #[test]
+    fn test_test_event_shape() {
+        let event = WebhookEvent::test(Utc::now());
+        let json = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(json["type"], "webhook.test");
+        assert!(json["id"].as_str().is_some_and(|id| !id.is_empty()));
+        assert!(json["data"].is_object());
+    }
+
+    #[actix_rt::test]
+    async fn test_deliver_without_url_fails() {
+        let service = WebhookService {
+            url: None,
+            signing_secret: String::new(),
+            client: reqwest::Client::new(),
+        };
+
+        assert!(!service.is_configured());
+        assert!(service
+            .deliver(&WebhookEvent::test(Utc::now()))
+            .await
+            .is_err());
+    }
+}
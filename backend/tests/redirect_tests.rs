@@ -1,6 +1,4 @@
 use actix_web::{test, web, App, http::StatusCode, HttpResponse, Result};
-use serde_json;
-use std::env;
 
 /// Mock handler functions for testing
 async fn mock_redirect_url(path: web::Path<String>) -> Result<HttpResponse> {
@@ -0,0 +1,25 @@
+-- Migration 004: Add media_type column to urls table
+-- Created: 2026-10-17
+-- Description: Stores a coarse media category (video, image, document, ...) for each link's target,
+-- populated asynchronously from the target's Content-Type when media type enrichment is enabled
+
+IF NOT EXISTS (SELECT * FROM sys.columns WHERE object_id = OBJECT_ID('urls') AND name = 'media_type')
+BEGIN
+    ALTER TABLE urls ADD media_type NVARCHAR(32) NULL;
+
+    PRINT 'Column media_type added to urls table.';
+END
+ELSE
+BEGIN
+    PRINT 'Column media_type already exists on urls table.';
+END
+GO
+
+-- Index for filtering links by media type in stats
+IF NOT EXISTS (SELECT * FROM sys.indexes WHERE name = 'IX_urls_media_type')
+BEGIN
+    CREATE INDEX IX_urls_media_type ON urls(media_type);
+
+    PRINT 'Index IX_urls_media_type created successfully.';
+END
+GO
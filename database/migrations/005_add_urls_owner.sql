@@ -0,0 +1,51 @@
+-- Migration 005: Record the owner and short link base URL on urls
+-- Created: 2026-10-17
+-- Description: Adds user_id (the signed-in user who created the link) and base_url (the scheme and
+-- domain the short link was issued on) so a user's links can be listed and exported
+
+IF NOT EXISTS (SELECT * FROM sys.columns WHERE object_id = OBJECT_ID('urls') AND name = 'user_id')
+BEGIN
+    ALTER TABLE urls ADD user_id BIGINT NULL; -- NULL for links created anonymously
+
+    PRINT 'Column user_id added to urls table.';
+END
+ELSE
+BEGIN
+    PRINT 'Column user_id already exists on urls table.';
+END
+GO
+
+IF NOT EXISTS (SELECT * FROM sys.columns WHERE object_id = OBJECT_ID('urls') AND name = 'base_url')
+BEGIN
+    ALTER TABLE urls ADD base_url NVARCHAR(300) NULL; -- e.g. https://go.example.com
+
+    PRINT 'Column base_url added to urls table.';
+END
+ELSE
+BEGIN
+    PRINT 'Column base_url already exists on urls table.';
+END
+GO
+
+IF NOT EXISTS (SELECT * FROM sys.foreign_keys WHERE name = 'FK_urls_user_id')
+BEGIN
+    ALTER TABLE urls
+    ADD CONSTRAINT FK_urls_user_id
+    FOREIGN KEY (user_id) REFERENCES users(id);
+
+    PRINT 'Foreign key constraint FK_urls_user_id added to urls table.';
+END
+ELSE
+BEGIN
+    PRINT 'Foreign key constraint FK_urls_user_id already exists.';
+END
+GO
+
+-- Index for listing a user's links
+IF NOT EXISTS (SELECT * FROM sys.indexes WHERE name = 'IX_urls_user_id')
+BEGIN
+    CREATE INDEX IX_urls_user_id ON urls(user_id);
+
+    PRINT 'Index IX_urls_user_id created successfully.';
+END
+GO
@@ -0,0 +1,16 @@
+-- Migration 006: Add is_default flag to domains table
+-- Created: 2026-10-17
+-- Description: Lets a verified domain be explicitly marked as the default used for new short links
+-- (see DEFAULT_DOMAIN_STRATEGY=explicit-default-flag)
+
+IF NOT EXISTS (SELECT * FROM sys.columns WHERE object_id = OBJECT_ID('domains') AND name = 'is_default')
+BEGIN
+    ALTER TABLE domains ADD is_default BIT NOT NULL CONSTRAINT DF_domains_is_default DEFAULT 0;
+
+    PRINT 'Column is_default added to domains table.';
+END
+ELSE
+BEGIN
+    PRINT 'Column is_default already exists on domains table.';
+END
+GO
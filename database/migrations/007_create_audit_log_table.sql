@@ -0,0 +1,28 @@
+-- Migration 007: Create audit_log table
+-- Created: 2026-10-17
+-- Description: Records administrative and security-sensitive actions (who did what, and when)
+
+IF NOT EXISTS (SELECT * FROM sys.tables WHERE name = 'audit_log')
+BEGIN
+    CREATE TABLE audit_log (
+        id BIGINT IDENTITY(1,1) PRIMARY KEY,
+        user_id BIGINT NULL, -- user who performed the action
+        action NVARCHAR(100) NOT NULL, -- e.g. domains.regenerate_tokens
+        details NVARCHAR(MAX) NULL, -- JSON description of the affected records
+        created_at DATETIME2 DEFAULT GETUTCDATE(),
+        CONSTRAINT FK_audit_log_user_id FOREIGN KEY (user_id) REFERENCES users(id)
+    );
+
+    -- Index for reviewing a user's actions
+    CREATE INDEX IX_audit_log_user_id ON audit_log(user_id);
+
+    -- Index for reviewing actions chronologically
+    CREATE INDEX IX_audit_log_created_at ON audit_log(created_at);
+
+    PRINT 'Audit log table and indexes created successfully.';
+END
+ELSE
+BEGIN
+    PRINT 'Audit log table already exists.';
+END
+GO
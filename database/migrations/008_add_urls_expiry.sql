@@ -0,0 +1,28 @@
+-- Migration 008: Add expiry columns to urls table
+-- Created: 2026-10-17
+-- Description: Optional link expiry, plus a per-link grace window during which an expired link
+-- shows a renewal page instead of redirecting (see EXPIRED_LINK_GRACE_SECONDS)
+
+IF NOT EXISTS (SELECT * FROM sys.columns WHERE object_id = OBJECT_ID('urls') AND name = 'expires_at')
+BEGIN
+    ALTER TABLE urls ADD expires_at DATETIME2 NULL;
+
+    PRINT 'Column expires_at added to urls table.';
+END
+ELSE
+BEGIN
+    PRINT 'Column expires_at already exists on urls table.';
+END
+GO
+
+IF NOT EXISTS (SELECT * FROM sys.columns WHERE object_id = OBJECT_ID('urls') AND name = 'expiry_grace_seconds')
+BEGIN
+    ALTER TABLE urls ADD expiry_grace_seconds BIGINT NULL;
+
+    PRINT 'Column expiry_grace_seconds added to urls table.';
+END
+ELSE
+BEGIN
+    PRINT 'Column expiry_grace_seconds already exists on urls table.';
+END
+GO
@@ -0,0 +1,16 @@
+-- Migration 009: Add rate_limit_tier to users table
+-- Created: 2026-10-17
+-- Description: Named rate limit tier per user (free, pro, enterprise); per-minute limits for each
+-- tier are configured with RATE_LIMIT_TIER_*_PER_MINUTE
+
+IF NOT EXISTS (SELECT * FROM sys.columns WHERE object_id = OBJECT_ID('users') AND name = 'rate_limit_tier')
+BEGIN
+    ALTER TABLE users ADD rate_limit_tier NVARCHAR(32) NOT NULL CONSTRAINT DF_users_rate_limit_tier DEFAULT 'free';
+
+    PRINT 'Column rate_limit_tier added to users table.';
+END
+ELSE
+BEGIN
+    PRINT 'Column rate_limit_tier already exists on users table.';
+END
+GO
@@ -0,0 +1,21 @@
+-- Migration 010: Create url_clicks_daily table
+-- Created: 2026-10-17
+-- Description: Redirect counts per link per UTC day, used for daily link statistics
+
+IF NOT EXISTS (SELECT * FROM sys.tables WHERE name = 'url_clicks_daily')
+BEGIN
+    CREATE TABLE url_clicks_daily (
+        url_id BIGINT NOT NULL,
+        day DATE NOT NULL,
+        clicks BIGINT NOT NULL CONSTRAINT DF_url_clicks_daily_clicks DEFAULT 0,
+        CONSTRAINT PK_url_clicks_daily PRIMARY KEY (url_id, day),
+        CONSTRAINT FK_url_clicks_daily_url_id FOREIGN KEY (url_id) REFERENCES urls(id) ON DELETE CASCADE
+    );
+
+    PRINT 'Table url_clicks_daily created successfully.';
+END
+ELSE
+BEGIN
+    PRINT 'Table url_clicks_daily already exists.';
+END
+GO
@@ -0,0 +1,16 @@
+-- Migration 011: Add creator_ip to urls table
+-- Created: 2026-10-17
+-- Description: Client IP that created an anonymous link, shown only to admins for abuse investigation.
+-- Links created while signed in are attributed through user_id instead and leave this NULL
+
+IF NOT EXISTS (SELECT * FROM sys.columns WHERE object_id = OBJECT_ID('urls') AND name = 'creator_ip')
+BEGIN
+    ALTER TABLE urls ADD creator_ip NVARCHAR(45) NULL;
+
+    PRINT 'Column creator_ip added to urls table.';
+END
+ELSE
+BEGIN
+    PRINT 'Column creator_ip already exists on urls table.';
+END
+GO
@@ -0,0 +1,43 @@
+-- Migration 012: Create tags and url_tags tables
+-- Created: 2026-10-17
+-- Description: Per-user tags and the links they are attached to. Tag names are unique per owner
+-- (case-insensitive under the default collation); tags are created as links are tagged
+
+IF NOT EXISTS (SELECT * FROM sys.tables WHERE name = 'tags')
+BEGIN
+    CREATE TABLE tags (
+        id BIGINT IDENTITY(1,1) PRIMARY KEY,
+        user_id BIGINT NOT NULL,
+        name NVARCHAR(50) NOT NULL,
+        created_at DATETIME2 NOT NULL CONSTRAINT DF_tags_created_at DEFAULT GETUTCDATE(),
+        CONSTRAINT UQ_tags_user_id_name UNIQUE (user_id, name),
+        CONSTRAINT FK_tags_user_id FOREIGN KEY (user_id) REFERENCES users(id)
+    );
+
+    PRINT 'Table tags created successfully.';
+END
+ELSE
+BEGIN
+    PRINT 'Table tags already exists.';
+END
+GO
+
+IF NOT EXISTS (SELECT * FROM sys.tables WHERE name = 'url_tags')
+BEGIN
+    CREATE TABLE url_tags (
+        url_id BIGINT NOT NULL,
+        tag_id BIGINT NOT NULL,
+        CONSTRAINT PK_url_tags PRIMARY KEY (url_id, tag_id),
+        CONSTRAINT FK_url_tags_url_id FOREIGN KEY (url_id) REFERENCES urls(id) ON DELETE CASCADE,
+        CONSTRAINT FK_url_tags_tag_id FOREIGN KEY (tag_id) REFERENCES tags(id)
+    );
+
+    CREATE INDEX IX_url_tags_tag_id ON url_tags(tag_id);
+
+    PRINT 'Table url_tags created successfully.';
+END
+ELSE
+BEGIN
+    PRINT 'Table url_tags already exists.';
+END
+GO
@@ -0,0 +1,25 @@
+-- Migration 013: Allow at most one default domain per owner
+-- Created: 2026-10-17
+-- Description: Filtered unique index backing the "exactly one default per owner" rule of
+-- POST /api/domains/{id}/default. Shared domains (user_id NULL) count as one owner. Extra defaults
+-- left by earlier concurrent switches are cleared first, keeping the oldest per owner.
+
+IF NOT EXISTS (SELECT * FROM sys.indexes WHERE object_id = OBJECT_ID('domains') AND name = 'UX_domains_user_id_default')
+BEGIN
+    WITH ranked AS (
+        SELECT is_default,
+               ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY created_at, id) AS position
+        FROM domains
+        WHERE is_default = 1
+    )
+    UPDATE ranked SET is_default = 0 WHERE position > 1;
+
+    CREATE UNIQUE INDEX UX_domains_user_id_default ON domains(user_id) WHERE is_default = 1;
+
+    PRINT 'Index UX_domains_user_id_default created on domains table.';
+END
+ELSE
+BEGIN
+    PRINT 'Index UX_domains_user_id_default already exists on domains table.';
+END
+GO